@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    gems::SpawnAnim,
+    music::{MusicController, TrackStarted},
+    settings::GameSettings,
+    spawn_table::SpawnTable,
+    spawning::{
+        clear_of_gems, clear_of_hazards, gem_height, GemBundle, Hazards, GEM_SPAWN_AHEAD,
+        PLACEMENT_ATTEMPTS,
+    },
+    Difficulty, GameRng, GameplaySet, Gem, MainCamera, GEM_SIZE,
+};
+
+/// Beats per minute for tracks without one in the settings, and while no
+/// music plays
+const DEFAULT_BPM: f32 = 120.0;
+/// Gems in each burst, spread evenly around a circle
+const BURST_GEMS: usize = 4;
+/// Radius of the circle, widened when the settings' gem separation needs
+/// more room between neighbours
+const BURST_RADIUS: f32 = 35.0;
+
+/// Plugin for the spawning mode that drops a cluster of gems on every beat
+/// of the background music, instead of streaming them in evenly
+pub struct BeatsPlugin;
+
+impl Plugin for BeatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BeatClock>().add_systems(
+            FixedUpdate,
+            (follow_track_bpm, spawn_beat_bursts)
+                .chain()
+                .in_set(GameplaySet::Spawn)
+                .run_if(spawns_on_beats),
+        );
+    }
+}
+
+/// Time to the next beat, at the tempo of the track that is playing
+#[derive(Resource)]
+pub struct BeatClock {
+    bpm: f32,
+    timer: Timer,
+}
+
+impl Default for BeatClock {
+    fn default() -> Self {
+        BeatClock::new(DEFAULT_BPM)
+    }
+}
+
+impl BeatClock {
+    fn new(bpm: f32) -> Self {
+        BeatClock {
+            bpm,
+            timer: Timer::from_seconds(60.0 / bpm, TimerMode::Repeating),
+        }
+    }
+}
+
+pub fn spawns_on_beats(settings: Res<GameSettings>) -> bool {
+    settings.beat_spawning
+}
+
+/// Keep the clock at the tempo of the current track, starting the beat over
+/// when a track starts or the tempo changes
+fn follow_track_bpm(
+    mut started: EventReader<TrackStarted>,
+    music: Option<Single<&MusicController>>,
+    settings: Res<GameSettings>,
+    mut clock: ResMut<BeatClock>,
+) {
+    let bpm = music
+        .and_then(|controller| settings.music_playlist.get(controller.current_index()))
+        .and_then(|track| settings.track_bpm.get(track))
+        .copied()
+        .filter(|bpm| *bpm > 0.0)
+        .unwrap_or(DEFAULT_BPM);
+    let track_started = started.read().count() > 0;
+    if track_started || bpm != clock.bpm {
+        *clock = BeatClock::new(bpm);
+    }
+}
+
+/// Drop a ring of gems off screen ahead of the camera on each beat, clear of
+/// the obstacles where there is room
+#[allow(clippy::too_many_arguments)]
+fn spawn_beat_bursts(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut clock: ResMut<BeatClock>,
+    table: Res<SpawnTable>,
+    difficulty: Res<Difficulty>,
+    mut rng: ResMut<GameRng>,
+    camera_transform: Single<&Transform, With<MainCamera>>,
+    hazards: Hazards,
+    gems: Query<&Transform, With<Gem>>,
+    settings: Res<GameSettings>,
+    time: Res<Time>,
+) {
+    let beats = clock.timer.tick(time.delta()).times_finished_this_tick();
+    if beats == 0 {
+        return;
+    }
+    let hazards = hazards.areas();
+    // bursts from this tick are added as they go, as the query can't see them
+    let mut placed: Vec<Vec2> = gems
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+
+    // neighbours in the ring are a chord apart, so grow the ring until that
+    // chord is at least the gem separation
+    let chord = 2.0 * (std::f32::consts::PI / BURST_GEMS as f32).sin();
+    let radius = BURST_RADIUS.max(settings.gem_separation / chord);
+
+    for _ in 0..beats {
+        let phase = rng.random::<f32>() * std::f32::consts::TAU;
+        let ring: Vec<Vec2> = (0..BURST_GEMS)
+            .map(|i| Vec2::from_angle(phase + std::f32::consts::TAU * i as f32 / BURST_GEMS as f32))
+            .map(|direction| direction * radius)
+            .collect();
+
+        // best effort: a crowded level can leave no clear height, and the
+        // burst still lands on the beat at the last one tried
+        let mut center = Vec2::new(camera_transform.translation.x + GEM_SPAWN_AHEAD, 0.0);
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            center.y = gem_height(&mut rng.rng, settings.gem_y_distribution);
+            let clear = ring.iter().all(|offset| {
+                clear_of_hazards(center + *offset, &hazards)
+                    && clear_of_gems(center + *offset, &placed, settings.gem_separation)
+            });
+            if clear {
+                break;
+            }
+        }
+
+        for offset in ring {
+            let position = center + offset;
+            placed.push(position);
+            let kind = table.random_gem(&mut rng.rng, **difficulty);
+            let gem = commands
+                .spawn(
+                    GemBundle::new(&asset_server, position, kind).with_value(table.gem_value(kind)),
+                )
+                .id();
+            if settings.graphics_quality.animate_gems() {
+                commands
+                    .entity(gem)
+                    .insert(SpawnAnim::new(Vec2::splat(GEM_SIZE)));
+            }
+        }
+    }
+}
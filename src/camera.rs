@@ -0,0 +1,358 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::{camera::Viewport, view::RenderLayers},
+    window::{PrimaryWindow, WindowResized},
+};
+
+use crate::{gems::GemKind, settings::GameSettings, CollisionEvent, GameState, MainCamera};
+
+/// Closest and furthest camera zoom, as orthographic scale
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 2.0;
+/// Scale change per key press or notch of the mouse wheel
+const ZOOM_STEP: f32 = 0.1;
+/// Pixels of touchpad scrolling that count as one notch of a mouse wheel
+const WHEEL_PIXELS_PER_NOTCH: f32 = 100.0;
+/// How quickly the zoom closes the gap to the chosen zoom, as a fraction per
+/// second
+const ZOOM_EASING: f32 = 10.0;
+/// Seconds the zoom has to stay put before it is saved, so scrolling through
+/// several notches writes the settings file once
+const ZOOM_SAVE_DELAY: f32 = 0.5;
+/// Fraction the view closes in by at the height of a zoom punch
+const ZOOM_PUNCH_AMOUNT: f32 = 0.08;
+/// Seconds a zoom punch takes to go in and back out
+const ZOOM_PUNCH_DURATION: f32 = 0.3;
+
+const LETTERBOX_COLOR: Color = Color::BLACK;
+
+/// Shakes per second while a camera shake lasts
+const SHAKE_FREQUENCY: f32 = 30.0;
+
+/// Plugin for player-controlled camera zoom and optional letterboxing
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingZoomSave>()
+            .init_resource::<ZoomPunch>()
+            .add_systems(Startup, spawn_letterbox)
+            .add_systems(
+                Update,
+                (
+                    (
+                        zoom_camera,
+                        punch_zoom,
+                        // menus keep the wheel, should they ever need to scroll
+                        wheel_zoom_camera.run_if(in_state(GameState::Playing)),
+                        apply_camera_zoom,
+                    )
+                        .chain(),
+                    save_zoom,
+                    update_letterbox,
+                    shake_camera,
+                ),
+            );
+    }
+}
+
+/// Short vertical shake of the main camera, fading out over `timer`.
+///
+/// Only the height is shaken, as following the player owns the camera's x.
+/// The shake is tracked as an offset so it adds to the danger nudge rather
+/// than replacing it.
+#[derive(Component)]
+pub struct CameraShake {
+    timer: Timer,
+    strength: f32,
+    offset: f32,
+}
+
+impl CameraShake {
+    pub fn new(duration: f32, strength: f32) -> Self {
+        CameraShake {
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+            strength,
+            offset: 0.0,
+        }
+    }
+}
+
+/// Time left before a zoom change is saved, see `ZOOM_SAVE_DELAY`
+#[derive(Resource, Default)]
+struct PendingZoomSave(Option<Timer>);
+
+impl PendingZoomSave {
+    /// Save after `ZOOM_SAVE_DELAY`, restarting the wait if one is running
+    fn schedule(&mut self) {
+        self.0 = Some(Timer::from_seconds(ZOOM_SAVE_DELAY, TimerMode::Once));
+    }
+}
+
+/// Quick zoom in and back out when a valuable gem is collected, on top of
+/// the eased zoom
+#[derive(Resource)]
+struct ZoomPunch {
+    amount: f32,
+    timer: Timer,
+    /// Multiplier the punch put on the projection's scale last frame
+    applied: f32,
+}
+
+impl Default for ZoomPunch {
+    fn default() -> Self {
+        ZoomPunch {
+            amount: 0.0,
+            timer: Timer::default(),
+            applied: 1.0,
+        }
+    }
+}
+
+/// Camera drawing only the letterbox bars, over the whole window
+#[derive(Component)]
+struct LetterboxCamera;
+
+/// One of the two bars filling the space around the playfield
+#[derive(Component)]
+enum LetterboxBar {
+    /// Left or top bar
+    Start,
+    /// Right or bottom bar
+    End,
+}
+
+/// `+` zooms in and `-` zooms out, saving the new zoom to the settings
+fn zoom_camera(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<GameSettings>,
+    mut pending_save: ResMut<PendingZoomSave>,
+) {
+    let mut change = 0.0;
+
+    if keyboard_input.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        change -= ZOOM_STEP;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        change += ZOOM_STEP;
+    }
+
+    if change == 0.0 {
+        return;
+    }
+
+    settings.camera_zoom = (settings.camera_zoom + change).clamp(MIN_ZOOM, MAX_ZOOM);
+    pending_save.schedule();
+}
+
+/// Scrolling up zooms in and down zooms out, saving the new zoom to the
+/// settings
+fn wheel_zoom_camera(
+    mut wheel: EventReader<MouseWheel>,
+    mut settings: ResMut<GameSettings>,
+    mut pending_save: ResMut<PendingZoomSave>,
+) {
+    let notches: f32 = wheel
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / WHEEL_PIXELS_PER_NOTCH,
+        })
+        .sum();
+    if notches == 0.0 {
+        return;
+    }
+
+    settings.camera_zoom = (settings.camera_zoom - notches * ZOOM_STEP).clamp(MIN_ZOOM, MAX_ZOOM);
+    pending_save.schedule();
+}
+
+/// Write the zoom to the settings file once it has settled. Timed in real
+/// time, so zooming while paused is saved too.
+fn save_zoom(
+    mut pending_save: ResMut<PendingZoomSave>,
+    settings: Res<GameSettings>,
+    time: Res<Time<Real>>,
+) {
+    let Some(timer) = pending_save.0.as_mut() else {
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        settings.save();
+        pending_save.0 = None;
+    }
+}
+
+/// Start a zoom punch when a rare or precious gem is collected
+fn punch_zoom(mut collisions: EventReader<CollisionEvent>, mut punch: ResMut<ZoomPunch>) {
+    let valuable = collisions
+        .read()
+        .filter(|collision| matches!(collision.kind, GemKind::Rare | GemKind::Precious))
+        .count();
+    if valuable > 0 {
+        punch.amount = ZOOM_PUNCH_AMOUNT;
+        punch.timer = Timer::from_seconds(ZOOM_PUNCH_DURATION, TimerMode::Once);
+    }
+}
+
+/// Ease the world camera's projection towards the chosen zoom, then apply
+/// any zoom punch. The HUD is laid out in screen space, so it is unaffected.
+fn apply_camera_zoom(
+    settings: Res<GameSettings>,
+    mut punch: ResMut<ZoomPunch>,
+    mut projection: Single<&mut OrthographicProjection, With<MainCamera>>,
+    time: Res<Time>,
+) {
+    // take last frame's punch back out, so only the chosen zoom is eased
+    let mut scale = projection.scale / punch.applied;
+    let target = settings.camera_zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    if scale != target {
+        let ease = (ZOOM_EASING * time.delta_secs()).min(1.0);
+        scale += (target - scale) * ease;
+        // snap once close enough, so the projection stops being marked changed
+        if (target - scale).abs() < 0.001 {
+            scale = target;
+        }
+    }
+
+    // in and back out, landing exactly on the eased zoom when it ends
+    let swell = if punch.timer.tick(time.delta()).finished() {
+        0.0
+    } else {
+        (punch.timer.fraction() * std::f32::consts::PI).sin()
+    };
+    punch.applied = 1.0 - punch.amount * swell;
+
+    let punched = scale * punch.applied;
+    if projection.scale != punched {
+        projection.scale = punched;
+    }
+}
+
+fn spawn_letterbox(mut commands: Commands) {
+    // Renders after the main camera without clearing it, and on its own
+    // render layer so it draws nothing but the bars
+    let camera = commands
+        .spawn((
+            Camera2d,
+            Camera {
+                order: 1,
+                clear_color: ClearColorConfig::None,
+                ..default()
+            },
+            RenderLayers::layer(1),
+            LetterboxCamera,
+        ))
+        .id();
+
+    for bar in [LetterboxBar::Start, LetterboxBar::End] {
+        commands.spawn((
+            Node {
+                display: Display::None,
+                ..default()
+            },
+            BackgroundColor(LETTERBOX_COLOR),
+            TargetCamera(camera),
+            bar,
+        ));
+    }
+}
+
+/// Largest viewport with the given aspect ratio, centered in the window
+fn letterbox_viewport(window_size: UVec2, aspect: f32) -> Viewport {
+    let window = window_size.as_vec2();
+    let size = if window.x / window.y > aspect {
+        Vec2::new(window.y * aspect, window.y)
+    } else {
+        Vec2::new(window.x, window.x / aspect)
+    };
+
+    Viewport {
+        physical_position: ((window - size) / 2.0).as_uvec2(),
+        physical_size: size.as_uvec2(),
+        ..default()
+    }
+}
+
+/// Fit the main camera's viewport to the configured aspect ratio and size the
+/// bars around it. The HUD is laid out in the main camera's viewport, so it
+/// stays anchored to the playfield.
+fn update_letterbox(
+    mut resized: EventReader<WindowResized>,
+    settings: Res<GameSettings>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut camera: Single<&mut Camera, With<MainCamera>>,
+    mut bars: Query<(&mut Node, &LetterboxBar)>,
+) {
+    if resized.read().count() == 0 && !settings.is_changed() {
+        return;
+    }
+
+    let window_size = window.physical_size();
+    let aspect = match settings.letterbox_aspect {
+        // a minimized window has no room for a viewport
+        Some(aspect) if aspect > 0.0 && window_size.x > 0 && window_size.y > 0 => aspect,
+        _ => {
+            camera.viewport = None;
+            for (mut node, _) in &mut bars {
+                node.display = Display::None;
+            }
+            return;
+        }
+    };
+
+    let viewport = letterbox_viewport(window_size, aspect);
+    let offset = viewport.physical_position.as_vec2() / window.scale_factor();
+    camera.viewport = Some(viewport);
+
+    let pillarbox = offset.x > 0.0;
+    for (mut node, bar) in &mut bars {
+        let mut bar_node = Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        };
+        match (pillarbox, bar) {
+            (true, LetterboxBar::Start) => {
+                bar_node.left = Val::Px(0.0);
+                bar_node.width = Val::Px(offset.x);
+            }
+            (true, LetterboxBar::End) => {
+                bar_node.right = Val::Px(0.0);
+                bar_node.width = Val::Px(offset.x);
+            }
+            (false, LetterboxBar::Start) => {
+                bar_node.top = Val::Px(0.0);
+                bar_node.height = Val::Px(offset.y);
+            }
+            (false, LetterboxBar::End) => {
+                bar_node.bottom = Val::Px(0.0);
+                bar_node.height = Val::Px(offset.y);
+            }
+        }
+        *node = bar_node;
+    }
+}
+
+fn shake_camera(
+    mut commands: Commands,
+    mut camera: Query<(Entity, &mut Transform, &mut CameraShake), With<MainCamera>>,
+    time: Res<Time>,
+) {
+    let Ok((entity, mut transform, mut shake)) = camera.get_single_mut() else {
+        return;
+    };
+
+    if shake.timer.tick(time.delta()).finished() {
+        transform.translation.y -= shake.offset;
+        commands.entity(entity).remove::<CameraShake>();
+        return;
+    }
+
+    let phase = shake.timer.elapsed_secs() * SHAKE_FREQUENCY * std::f32::consts::TAU;
+    let offset = phase.sin() * shake.strength * shake.timer.fraction_remaining();
+    transform.translation.y += offset - shake.offset;
+    shake.offset = offset;
+}
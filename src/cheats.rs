@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+
+use crate::{obstacles::Invulnerable, GameState, Player, RunFlags, Score};
+
+/// Typed while playing, after F3 so normal play never sets them off
+const GOD_MODE_CODE: [KeyCode; 4] = [KeyCode::F3, KeyCode::KeyG, KeyCode::KeyO, KeyCode::KeyD];
+const SCORE_CODE: [KeyCode; 4] = [KeyCode::F3, KeyCode::KeyG, KeyCode::KeyE, KeyCode::KeyM];
+
+/// Long enough to outlast any test run
+const GOD_MODE_DURATION: f32 = 3600.0;
+const CHEAT_SCORE: usize = 10_000;
+
+/// Plugin for cheat codes that skip ahead to late-game states while testing.
+/// Only built into debug builds, and runs using one aren't ranked.
+///
+/// - F3 G O D makes every rug invulnerable
+/// - F3 G E M adds `CHEAT_SCORE` points
+pub struct CheatsPlugin;
+
+impl Plugin for CheatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CheatInput>().add_systems(
+            Update,
+            match_cheat_codes.run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cheat {
+    GodMode,
+    Score,
+}
+
+impl Cheat {
+    const ALL: [Cheat; 2] = [Cheat::GodMode, Cheat::Score];
+
+    fn code(self) -> &'static [KeyCode] {
+        match self {
+            Cheat::GodMode => &GOD_MODE_CODE,
+            Cheat::Score => &SCORE_CODE,
+        }
+    }
+}
+
+/// How many keys of each cheat's code have been typed so far, in the order
+/// of `Cheat::ALL`
+#[derive(Resource, Default)]
+struct CheatInput([usize; Cheat::ALL.len()]);
+
+fn match_cheat_codes(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut input: ResMut<CheatInput>,
+    players: Query<Entity, With<Player>>,
+    mut score: ResMut<Score>,
+    mut flags: ResMut<RunFlags>,
+) {
+    for key in keyboard_input.get_just_pressed() {
+        for (cheat, progress) in Cheat::ALL.into_iter().zip(&mut input.0) {
+            let code = cheat.code();
+            // a wrong key starts over, counting it if it begins the code
+            *progress = if code[*progress] == *key {
+                *progress + 1
+            } else {
+                usize::from(code[0] == *key)
+            };
+            if *progress < code.len() {
+                continue;
+            }
+
+            *progress = 0;
+            info!("cheat {cheat:?} activated");
+            flags.cheated = true;
+            match cheat {
+                Cheat::GodMode => {
+                    for player in &players {
+                        commands
+                            .entity(player)
+                            .insert(Invulnerable::new(GOD_MODE_DURATION));
+                    }
+                }
+                Cheat::Score => **score += CHEAT_SCORE,
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use crate::{obstacles::Hitbox, Collider, Gem, Player, GEM_SIZE, PLAYER_HITBOX};
+
+const GEM_BOX_COLOR: Color = Color::srgb(0.2, 0.9, 1.0);
+const OBSTACLE_BOX_COLOR: Color = Color::srgb(1.0, 0.2, 0.2);
+const PLAYER_BOX_COLOR: Color = Color::srgb(0.2, 1.0, 0.2);
+
+/// Plugin for a debug view outlining the boxes collisions are checked
+/// against.
+///
+/// - F2 shows or hides the outlines
+pub struct ColliderDebugPlugin;
+
+impl Plugin for ColliderDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShowColliders>().add_systems(
+            Update,
+            (
+                toggle_colliders,
+                draw_colliders.run_if(resource_equals(ShowColliders(true))),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Whether the collider outlines are drawn; off until toggled
+#[derive(Resource, Default, PartialEq, Eq)]
+struct ShowColliders(bool);
+
+fn toggle_colliders(keyboard_input: Res<ButtonInput<KeyCode>>, mut show: ResMut<ShowColliders>) {
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        show.0 = !show.0;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn draw_colliders(
+    mut gizmos: Gizmos,
+    colliders: Query<(&Transform, Option<&Hitbox>, Has<Gem>), With<Collider>>,
+    players: Query<&Transform, With<Player>>,
+) {
+    for (transform, hitbox, is_gem) in &colliders {
+        let (size, color) = match hitbox {
+            Some(hitbox) => (**hitbox, OBSTACLE_BOX_COLOR),
+            None if is_gem => (
+                Vec2::splat(GEM_SIZE) * transform.scale.truncate(),
+                GEM_BOX_COLOR,
+            ),
+            None => continue,
+        };
+        gizmos.rect_2d(
+            Isometry2d::from_translation(transform.translation.truncate()),
+            size,
+            color,
+        );
+    }
+
+    for transform in &players {
+        gizmos.rect_2d(
+            Isometry2d::from_translation(transform.translation.truncate()),
+            PLAYER_HITBOX,
+            PLAYER_BOX_COLOR,
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+
+use crate::{
+    camera::CameraShake,
+    collect_gems, flash,
+    gems::GemKind,
+    settings::{GameSettings, Volumes},
+    setup, CollisionEvent, CollisionSound, GameState, GameplaySet, Gem, Health, HealthBarUi,
+    HealthChanged, HudText, MainCamera, Player, SideHud, HEALTH_BAR_COLOR,
+};
+
+/// Time allowed between pickups before the combo breaks
+pub const COMBO_WINDOW: f32 = 1.5;
+
+const COMBO_COLOR: Color = Color::srgb(1.0, 0.6, 0.2);
+/// Scale of the combo text just before the window runs out
+const COMBO_MIN_SCALE: f32 = 0.6;
+
+/// The combo break reuses the pickup clip, slowed down so it sounds like a
+/// descending "whoops"
+const COMBO_BREAK_SPEED: f32 = 0.6;
+const COMBO_BREAK_SHAKE_DURATION: f32 = 0.2;
+/// Kept small, so it doesn't feel like taking damage
+const COMBO_BREAK_SHAKE_STRENGTH: f32 = 3.0;
+
+/// Combo that sets off the vacuum, pulling in every gem on screen
+const VACUUM_COMBO: u32 = 15;
+const VACUUM_DURATION: f32 = 0.6;
+/// Speed gems fly towards the nearest rug while the vacuum runs
+const VACUUM_SPEED: f32 = 2500.0;
+/// Gems this far either side of the camera center count as on screen
+const VACUUM_RANGE: f32 = 800.0;
+const VACUUM_FLASH_COLOR: Color = Color::srgba(1.0, 0.8, 0.4, 0.35);
+const VACUUM_FLASH_DURATION: f32 = 0.4;
+/// The vacuum reuses the pickup clip, sped up into a rising "whoosh"
+const VACUUM_SOUND_SPEED: f32 = 1.8;
+
+/// Color the health bar flashes when a combo milestone heals the rugs
+const HEAL_FLASH_COLOR: Color = Color::srgb(1.0, 1.0, 1.0);
+const HEAL_FLASH_DURATION: f32 = 0.5;
+
+/// Plugin to count gems collected in quick succession
+pub struct ComboPlugin;
+
+impl Plugin for ComboPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Combo>()
+            .add_event::<ComboBroken>()
+            .add_event::<ComboMilestone>()
+            .add_systems(Startup, spawn_combo_ui.after(setup))
+            .add_systems(
+                FixedUpdate,
+                (
+                    vacuum_gems.before(collect_gems),
+                    (update_combo, heal_on_combo_milestone)
+                        .chain()
+                        .after(collect_gems),
+                )
+                    .in_set(GameplaySet::Collision),
+            )
+            .add_systems(Update, (react_to_combo_break, flash_health_bar))
+            .add_systems(
+                Update,
+                (update_combo_ui, animate_combo_ui)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Gems collected without letting `COMBO_WINDOW` pass between two pickups
+#[derive(Resource)]
+pub struct Combo {
+    pub count: u32,
+    pub window: Timer,
+    /// Time left on the vacuum set off by reaching `VACUUM_COMBO`
+    pub vacuum: Option<Timer>,
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        Combo {
+            count: 0,
+            window: Timer::from_seconds(COMBO_WINDOW, TimerMode::Once),
+            vacuum: None,
+        }
+    }
+}
+
+/// Sent when a combo of at least two gems runs out
+#[derive(Event)]
+pub struct ComboBroken {
+    pub count: u32,
+}
+
+/// Sent when the combo reaches one of the settings' combo heal milestones
+#[derive(Event)]
+pub struct ComboMilestone {
+    pub count: u32,
+}
+
+#[derive(Component)]
+struct ComboUi;
+
+#[derive(Component, Deref, DerefMut)]
+struct HealFlash(Timer);
+
+#[allow(clippy::too_many_arguments)]
+fn update_combo(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    mut broken: EventWriter<ComboBroken>,
+    mut milestones: EventWriter<ComboMilestone>,
+    mut combo: ResMut<Combo>,
+    sound: Res<CollisionSound>,
+    settings: Res<GameSettings>,
+    volumes: Volumes,
+    time: Res<Time>,
+) {
+    combo.window.tick(time.delta());
+
+    for _ in collisions.read() {
+        // gems pulled in by the vacuum are the finisher's reward rather than
+        // the start of a new combo, so one vacuum can't set off the next
+        if combo.vacuum.is_some() {
+            continue;
+        }
+        combo.count += 1;
+        combo.window.reset();
+        if settings.combo_heal_milestones.contains(&combo.count) {
+            milestones.send(ComboMilestone { count: combo.count });
+        }
+    }
+
+    // the finisher cashes the combo in rather than letting it break
+    if combo.count >= VACUUM_COMBO {
+        combo.count = 0;
+        combo.vacuum = Some(Timer::from_seconds(VACUUM_DURATION, TimerMode::Once));
+
+        flash::spawn_screen_flash(&mut commands, VACUUM_FLASH_COLOR, VACUUM_FLASH_DURATION);
+        commands.spawn((
+            AudioPlayer(sound.clone()),
+            PlaybackSettings::DESPAWN
+                .with_speed(VACUUM_SOUND_SPEED)
+                .with_volume(volumes.sfx()),
+        ));
+        return;
+    }
+
+    if combo.count > 0 && combo.window.finished() {
+        if combo.count >= 2 {
+            broken.send(ComboBroken { count: combo.count });
+        }
+        combo.count = 0;
+    }
+}
+
+/// While the vacuum runs, fly every gem on screen towards the nearest rug to
+/// be collected. Mimics are left alone, as pulling them in would hurt.
+#[allow(clippy::type_complexity)]
+fn vacuum_gems(
+    mut combo: ResMut<Combo>,
+    players: Query<&Transform, With<Player>>,
+    mut gems: Query<(&mut Transform, &GemKind), (With<Gem>, Without<Player>)>,
+    camera: Single<&Transform, (With<MainCamera>, Without<Gem>, Without<Player>)>,
+    time: Res<Time>,
+) {
+    let Some(vacuum) = combo.vacuum.as_mut() else {
+        return;
+    };
+    if vacuum.tick(time.delta()).finished() {
+        combo.vacuum = None;
+        return;
+    }
+
+    let step = VACUUM_SPEED * time.delta_secs();
+    for (mut transform, kind) in &mut gems {
+        if *kind == GemKind::Mimic
+            || (transform.translation.x - camera.translation.x).abs() > VACUUM_RANGE
+        {
+            continue;
+        }
+
+        let gem_pos = transform.translation.truncate();
+        let Some(target) = players
+            .iter()
+            .map(|player| player.translation.truncate())
+            .min_by(|a, b| a.distance(gem_pos).total_cmp(&b.distance(gem_pos)))
+        else {
+            return;
+        };
+
+        let to_target = target - gem_pos;
+        let moved = to_target.clamp_length_max(step);
+        transform.translation += moved.extend(0.0);
+    }
+}
+
+/// Give every rug a hit point back for reaching a combo milestone, up to
+/// its maximum health, and flash the health bar
+fn heal_on_combo_milestone(
+    mut commands: Commands,
+    mut milestones: EventReader<ComboMilestone>,
+    mut players: Query<&mut Health, With<Player>>,
+    health_bar: Single<Entity, With<HealthBarUi>>,
+) {
+    for milestone in milestones.read() {
+        debug!(count = milestone.count, "combo milestone");
+        for mut health in &mut players {
+            health.current = (health.current + 1).min(health.max);
+            commands.send_event(HealthChanged {
+                current: health.current,
+                max: health.max,
+            });
+        }
+        commands
+            .entity(*health_bar)
+            .insert(HealFlash(Timer::from_seconds(
+                HEAL_FLASH_DURATION,
+                TimerMode::Once,
+            )));
+    }
+}
+
+fn flash_health_bar(
+    mut commands: Commands,
+    mut bar: Query<(Entity, &mut HealFlash, &mut BackgroundColor)>,
+    time: Res<Time>,
+) {
+    for (entity, mut flash, mut color) in &mut bar {
+        if flash.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<HealFlash>();
+            color.0 = HEALTH_BAR_COLOR;
+            continue;
+        }
+
+        color.0 = HEALTH_BAR_COLOR.mix(&HEAL_FLASH_COLOR, flash.fraction_remaining());
+    }
+}
+
+/// Play a "whoops" and shake the camera a little when a combo is lost
+fn react_to_combo_break(
+    mut commands: Commands,
+    mut broken: EventReader<ComboBroken>,
+    settings: Res<GameSettings>,
+    volumes: Volumes,
+    sound: Res<CollisionSound>,
+    camera: Single<Entity, With<MainCamera>>,
+) {
+    if broken.is_empty() {
+        return;
+    }
+    broken.clear();
+    if !settings.combo_break_feedback {
+        return;
+    }
+
+    commands.spawn((
+        AudioPlayer(sound.clone()),
+        PlaybackSettings::DESPAWN
+            .with_speed(COMBO_BREAK_SPEED)
+            .with_volume(volumes.sfx()),
+    ));
+    commands.entity(*camera).insert(CameraShake::new(
+        COMBO_BREAK_SHAKE_DURATION,
+        COMBO_BREAK_SHAKE_STRENGTH,
+    ));
+}
+
+fn spawn_combo_ui(mut commands: Commands, side_hud: Single<Entity, With<SideHud>>) {
+    commands.entity(*side_hud).with_child((
+        Text::default(),
+        TextFont::default(),
+        HudText(1.0),
+        TextColor(COMBO_COLOR),
+        Visibility::Hidden,
+        ComboUi,
+    ));
+}
+
+fn update_combo_ui(combo: Res<Combo>, mut ui: Single<(&mut Text, &mut Visibility), With<ComboUi>>) {
+    if !combo.is_changed() {
+        return;
+    }
+
+    let (ref mut text, ref mut visibility) = *ui;
+    // a single gem isn't a combo yet
+    if combo.count < 2 {
+        **visibility = Visibility::Hidden;
+        return;
+    }
+
+    **visibility = Visibility::Inherited;
+    text.0 = format!("Combo x{}", combo.count);
+}
+
+/// Shrink and fade the combo text as the window runs out. Each pickup resets
+/// the window, which snaps it back to full size.
+fn animate_combo_ui(
+    combo: Res<Combo>,
+    mut ui: Single<(&mut Transform, &mut TextColor), With<ComboUi>>,
+) {
+    let remaining = combo.window.fraction_remaining();
+    let (ref mut transform, ref mut color) = *ui;
+
+    transform.scale = Vec3::splat(COMBO_MIN_SCALE + (1.0 - COMBO_MIN_SCALE) * remaining);
+    color.0 = COMBO_COLOR.with_alpha(remaining);
+}
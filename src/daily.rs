@@ -0,0 +1,114 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+
+use crate::{save::SaveData, GameMode, GameState, HudText, RunFlags, Score, TEXT_COLOR};
+
+/// Plugin to record and show daily challenge results
+pub struct DailyPlugin;
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::GameOver),
+            (record_daily_score, show_daily_result)
+                .chain()
+                .run_if(resource_equals(GameMode::Daily)),
+        );
+    }
+}
+
+/// A calendar date in UTC, so every player gets the same daily challenge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Date {
+    year: i64,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    /// Seed for the day's layout, e.g. 20261014
+    pub fn seed(&self) -> u64 {
+        (self.year * 10000 + self.month as i64 * 100 + self.day as i64) as u64
+    }
+
+    /// Convert a count of days since 1970-01-01 to a date
+    ///
+    /// Uses Howard Hinnant's `civil_from_days` algorithm.
+    fn from_days_since_epoch(days: i64) -> Date {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        // months counted from March, so the leap day is last
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Date {
+            year,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Today's date in UTC
+pub fn today() -> Date {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    Date::from_days_since_epoch((seconds / 86_400) as i64)
+}
+
+fn record_daily_score(score: Res<Score>, flags: Res<RunFlags>, mut save: ResMut<SaveData>) {
+    if flags.cheated {
+        return;
+    }
+    let best = save.daily_best.entry(today().to_string()).or_default();
+    if **score > *best {
+        *best = **score;
+        save.save();
+    }
+}
+
+/// Show a shareable summary of today's challenge on the game over screen
+fn show_daily_result(mut commands: Commands, score: Res<Score>, save: Res<SaveData>) {
+    let date = today().to_string();
+    let best = save.daily_best.get(&date).copied().unwrap_or_default();
+
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::new(format!(
+            "Magic Rug daily {date}: {} points (best {best})",
+            **score
+        )),
+        TextFont::default(),
+        HudText(1.0),
+        TextColor(TEXT_COLOR),
+        StateScoped(GameState::GameOver),
+    ));
+}
@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+
+use crate::{
+    collect_gems, setup, GameState, GameplaySet, HudText, SideHud, STAMINA_BAR_BACKGROUND,
+    TEXT_COLOR,
+};
+
+/// Seconds gem values stay multiplied after collecting a double points gem
+pub const DOUBLE_POINTS_DURATION: f32 = 8.0;
+const DOUBLE_POINTS_FACTOR: f32 = 2.0;
+
+const DOUBLE_POINTS_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+const DOUBLE_POINTS_BAR_SIZE: Vec2 = Vec2::new(150.0, 10.0);
+
+/// Plugin for the rare gems that make every gem worth more for a while
+pub struct DoublePointsPlugin;
+
+impl Plugin for DoublePointsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScoreMultiplier>()
+            .add_event::<DoublePointsCollected>()
+            .add_systems(Startup, spawn_double_points_ui.after(setup))
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_score_multiplier.in_set(GameplaySet::Movement),
+                    start_double_points
+                        .after(collect_gems)
+                        .in_set(GameplaySet::Collision),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    tint_double_points_gems,
+                    update_double_points_ui.run_if(in_state(GameState::Playing)),
+                ),
+            );
+    }
+}
+
+/// A gem that multiplies gem values for `DOUBLE_POINTS_DURATION` when
+/// collected
+#[derive(Component)]
+pub struct DoublePoints;
+
+/// A double points gem was collected
+#[derive(Event)]
+pub struct DoublePointsCollected;
+
+/// Factor gem values are multiplied by until the timer runs out. The default
+/// timer has no time on it, so gems are worth their usual amount.
+#[derive(Resource)]
+pub struct ScoreMultiplier {
+    pub factor: f32,
+    pub timer: Timer,
+}
+
+impl Default for ScoreMultiplier {
+    fn default() -> Self {
+        ScoreMultiplier {
+            factor: DOUBLE_POINTS_FACTOR,
+            timer: Timer::default(),
+        }
+    }
+}
+
+impl ScoreMultiplier {
+    pub fn is_active(&self) -> bool {
+        self.timer.remaining_secs() > 0.0
+    }
+
+    /// What gem values are multiplied by right now
+    pub fn current(&self) -> f32 {
+        if self.is_active() {
+            self.factor
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Component)]
+struct DoublePointsUi;
+
+#[derive(Component)]
+struct DoublePointsBar;
+
+fn tick_score_multiplier(mut multiplier: ResMut<ScoreMultiplier>, time: Res<Time>) {
+    if multiplier.is_active() {
+        multiplier.timer.tick(time.delta());
+    }
+}
+
+/// Another gem while it's active starts the time over, rather than
+/// multiplying the factor again
+fn start_double_points(
+    mut events: EventReader<DoublePointsCollected>,
+    mut multiplier: ResMut<ScoreMultiplier>,
+) {
+    if events.read().count() > 0 {
+        multiplier.timer = Timer::from_seconds(DOUBLE_POINTS_DURATION, TimerMode::Once);
+    }
+}
+
+fn tint_double_points_gems(mut gems: Query<&mut Sprite, Added<DoublePoints>>) {
+    for mut sprite in &mut gems {
+        sprite.color = DOUBLE_POINTS_COLOR;
+    }
+}
+
+fn spawn_double_points_ui(mut commands: Commands, side_hud: Single<Entity, With<SideHud>>) {
+    commands.entity(*side_hud).with_children(|p| {
+        p.spawn((
+            Node {
+                column_gap: Val::Px(6.0),
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            Visibility::Hidden,
+            DoublePointsUi,
+        ))
+        .with_children(|row| {
+            row.spawn((
+                Text::new(format!("x{DOUBLE_POINTS_FACTOR}")),
+                TextFont::default(),
+                HudText(0.75),
+                TextColor(TEXT_COLOR),
+            ));
+            row.spawn((
+                Node {
+                    width: Val::Px(DOUBLE_POINTS_BAR_SIZE.x),
+                    height: Val::Px(DOUBLE_POINTS_BAR_SIZE.y),
+                    ..default()
+                },
+                BackgroundColor(STAMINA_BAR_BACKGROUND),
+            ))
+            .with_child((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(DOUBLE_POINTS_COLOR),
+                DoublePointsBar,
+            ));
+        });
+    });
+}
+
+fn update_double_points_ui(
+    multiplier: Res<ScoreMultiplier>,
+    mut ui: Single<&mut Visibility, With<DoublePointsUi>>,
+    mut bar: Single<&mut Node, With<DoublePointsBar>>,
+) {
+    if !multiplier.is_active() {
+        **ui = Visibility::Hidden;
+        return;
+    }
+
+    **ui = Visibility::Inherited;
+    bar.width = Val::Percent(multiplier.timer.fraction_remaining() * 100.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn collect_double_points(world: &mut World) {
+        world.send_event(DoublePointsCollected);
+        world.run_system_once(start_double_points).unwrap();
+    }
+
+    #[test]
+    fn second_gem_refreshes_rather_than_stacks() {
+        let mut world = World::new();
+        world.init_resource::<Events<DoublePointsCollected>>();
+        world.init_resource::<ScoreMultiplier>();
+        assert_eq!(world.resource::<ScoreMultiplier>().current(), 1.0);
+
+        collect_double_points(&mut world);
+        assert_eq!(
+            world.resource::<ScoreMultiplier>().current(),
+            DOUBLE_POINTS_FACTOR
+        );
+
+        world
+            .resource_mut::<ScoreMultiplier>()
+            .timer
+            .tick(Duration::from_secs_f32(DOUBLE_POINTS_DURATION / 2.0));
+        collect_double_points(&mut world);
+
+        let multiplier = world.resource::<ScoreMultiplier>();
+        assert_eq!(multiplier.current(), DOUBLE_POINTS_FACTOR);
+        assert_eq!(multiplier.timer.remaining_secs(), DOUBLE_POINTS_DURATION);
+
+        let mut multiplier = world.resource_mut::<ScoreMultiplier>();
+        multiplier
+            .timer
+            .tick(Duration::from_secs_f32(DOUBLE_POINTS_DURATION));
+        assert_eq!(multiplier.current(), 1.0);
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    combo::ComboBroken, emit_state_changes, CollisionEvent, GameStateChanged, HealthChanged,
+};
+
+/// Entries kept before the oldest are dropped
+const EVENT_LOG_CAPACITY: usize = 200;
+/// Entries shown at once
+const EVENT_LOG_LINES: usize = 12;
+const EVENT_LOG_FONT_SIZE: f32 = 14.0;
+const EVENT_LOG_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.7);
+
+/// Plugin for a debug overlay listing recent gameplay events.
+///
+/// - F1 shows or hides the log
+/// - PageUp / PageDown scroll through older entries
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EventLog>()
+            .add_systems(Startup, spawn_event_log_ui)
+            .add_systems(
+                Update,
+                (
+                    (
+                        log_collections,
+                        log_health_changes,
+                        log_combo_breaks,
+                        log_state_changes.after(emit_state_changes),
+                    ),
+                    (toggle_event_log, update_event_log_ui).chain(),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Recent gameplay events, newest last, with the time they happened
+#[derive(Resource, Default)]
+pub struct EventLog {
+    entries: VecDeque<(f32, String)>,
+    /// How many entries up from the newest the view is scrolled
+    scroll: usize,
+}
+
+impl EventLog {
+    pub fn push(&mut self, time: &Time, message: impl Into<String>) {
+        if self.entries.len() == EVENT_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries
+            .push_back((time.elapsed_secs(), message.into()));
+    }
+}
+
+#[derive(Component)]
+struct EventLogUi;
+
+fn spawn_event_log_ui(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            left: Val::Px(5.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        Text::default(),
+        TextFont {
+            font_size: EVENT_LOG_FONT_SIZE,
+            ..default()
+        },
+        BackgroundColor(EVENT_LOG_BACKGROUND),
+        // Off by default
+        Visibility::Hidden,
+        EventLogUi,
+    ));
+}
+
+fn log_collections(
+    mut log: ResMut<EventLog>,
+    mut collisions: EventReader<CollisionEvent>,
+    time: Res<Time>,
+) {
+    for collision in collisions.read() {
+        log.push(
+            &time,
+            format!(
+                "collected {} gem at ({:.0}, {:.0})",
+                collision.kind.name(),
+                collision.position.x,
+                collision.position.y
+            ),
+        );
+    }
+}
+
+fn log_health_changes(
+    mut log: ResMut<EventLog>,
+    mut health_changes: EventReader<HealthChanged>,
+    time: Res<Time>,
+) {
+    for change in health_changes.read() {
+        log.push(
+            &time,
+            format!("health is {}/{}", change.current, change.max),
+        );
+    }
+}
+
+fn log_combo_breaks(
+    mut log: ResMut<EventLog>,
+    mut broken: EventReader<ComboBroken>,
+    time: Res<Time>,
+) {
+    for combo in broken.read() {
+        log.push(&time, format!("combo of {} broken", combo.count));
+    }
+}
+
+fn log_state_changes(
+    mut log: ResMut<EventLog>,
+    mut changes: EventReader<GameStateChanged>,
+    time: Res<Time>,
+) {
+    for change in changes.read() {
+        log.push(&time, format!("state {:?} -> {:?}", change.from, change.to));
+    }
+}
+
+fn toggle_event_log(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut log: ResMut<EventLog>,
+    mut visibility: Single<&mut Visibility, With<EventLogUi>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        **visibility = match **visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+
+    let max_scroll = log.entries.len().saturating_sub(EVENT_LOG_LINES);
+    if keyboard_input.just_pressed(KeyCode::PageUp) {
+        log.scroll = (log.scroll + EVENT_LOG_LINES).min(max_scroll);
+    } else if keyboard_input.just_pressed(KeyCode::PageDown) {
+        log.scroll = log.scroll.saturating_sub(EVENT_LOG_LINES);
+    }
+}
+
+fn update_event_log_ui(
+    log: Res<EventLog>,
+    mut ui: Single<(&mut Text, Ref<Visibility>), With<EventLogUi>>,
+) {
+    let (ref mut text, ref visibility) = *ui;
+    if **visibility == Visibility::Hidden || !(log.is_changed() || visibility.is_changed()) {
+        return;
+    }
+
+    let end = log.entries.len() - log.scroll.min(log.entries.len());
+    let start = end.saturating_sub(EVENT_LOG_LINES);
+    text.0 = log
+        .entries
+        .range(start..end)
+        .map(|(at, message)| format!("[{at:8.2}] {message}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+}
@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+
+use crate::{
+    check_player_death, collect_gems, obstacles::Invulnerable, settings::Volumes, CollisionSound,
+    GameState, GameplaySet, Health, HealthChanged, HudText, Player, RunEntity,
+};
+
+/// Most spare lives that can be banked at once
+pub const MAX_LIVES: u32 = 3;
+
+const EXTRA_LIFE_COLOR: Color = Color::srgb(1.0, 0.4, 0.7);
+/// The jingle reuses the pickup clip, played back slower than the milestone
+/// fanfare so the two are told apart
+const EXTRA_LIFE_SOUND_SPEED: f32 = 0.75;
+const POPUP_DURATION: f32 = 1.5;
+
+/// Plugin for the very rare gems that bank a spare life, up to
+/// `MAX_LIVES`. A rug that runs out of health spends one to carry on.
+pub struct ExtraLifePlugin;
+
+impl Plugin for ExtraLifePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Lives>()
+            .add_event::<ExtraLifeCollected>()
+            .add_systems(
+                FixedUpdate,
+                grant_extra_life
+                    .after(collect_gems)
+                    .in_set(GameplaySet::Collision),
+            )
+            .add_systems(
+                Update,
+                (
+                    spend_lives
+                        .before(check_player_death)
+                        .run_if(in_state(GameState::Playing)),
+                    tint_extra_life_gems,
+                    celebrate_extra_life,
+                    fade_popups,
+                ),
+            );
+    }
+}
+
+/// Spare lives banked in the current run, shared by the rugs
+#[derive(Resource, Deref, DerefMut, Default)]
+pub struct Lives(u32);
+
+/// A gem that banks a spare life when collected
+#[derive(Component)]
+pub struct ExtraLife;
+
+/// An extra life gem was collected
+#[derive(Event)]
+pub struct ExtraLifeCollected;
+
+/// Celebration text, gone once the timer runs out
+#[derive(Component, Deref, DerefMut)]
+struct ExtraLifePopup(Timer);
+
+fn grant_extra_life(mut events: EventReader<ExtraLifeCollected>, mut lives: ResMut<Lives>) {
+    for _ in events.read() {
+        **lives = (**lives + 1).min(MAX_LIVES);
+    }
+}
+
+/// Bring back a rug that ran out of health at full health, if there is a
+/// spare life for it
+fn spend_lives(
+    mut commands: Commands,
+    mut players: Query<(Entity, &mut Health), With<Player>>,
+    mut lives: ResMut<Lives>,
+) {
+    for (entity, mut health) in &mut players {
+        if health.current > 0 || **lives == 0 {
+            continue;
+        }
+
+        **lives -= 1;
+        health.current = health.max;
+        health.partial = 0.0;
+        commands.entity(entity).insert(Invulnerable::after_hit());
+        commands.send_event(HealthChanged {
+            current: health.current,
+            max: health.max,
+        });
+    }
+}
+
+fn tint_extra_life_gems(mut gems: Query<&mut Sprite, Added<ExtraLife>>) {
+    for mut sprite in &mut gems {
+        sprite.color = EXTRA_LIFE_COLOR;
+    }
+}
+
+fn celebrate_extra_life(
+    mut commands: Commands,
+    mut events: EventReader<ExtraLifeCollected>,
+    sound: Res<CollisionSound>,
+    volumes: Volumes,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    commands.spawn((
+        AudioPlayer(sound.clone()),
+        PlaybackSettings::DESPAWN
+            .with_speed(EXTRA_LIFE_SOUND_SPEED)
+            .with_volume(volumes.sfx()),
+    ));
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(30.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::new("EXTRA LIFE!"),
+        TextFont::default(),
+        HudText(3.0),
+        TextColor(EXTRA_LIFE_COLOR),
+        ExtraLifePopup(Timer::from_seconds(POPUP_DURATION, TimerMode::Once)),
+        RunEntity,
+    ));
+}
+
+fn fade_popups(
+    mut commands: Commands,
+    mut popups: Query<(Entity, &mut ExtraLifePopup, &mut TextColor)>,
+    time: Res<Time>,
+) {
+    for (entity, mut popup, mut color) in &mut popups {
+        if popup.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        color.0 = EXTRA_LIFE_COLOR.with_alpha(popup.fraction_remaining());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::MAX_HEALTH;
+
+    #[test]
+    fn extra_life_adds_a_life_up_to_the_cap() {
+        let mut world = World::new();
+        world.init_resource::<Events<ExtraLifeCollected>>();
+        world.init_resource::<Lives>();
+
+        world.send_event(ExtraLifeCollected);
+        world.run_system_once(grant_extra_life).unwrap();
+        assert_eq!(**world.resource::<Lives>(), 1);
+
+        for _ in 0..MAX_LIVES {
+            world.send_event(ExtraLifeCollected);
+        }
+        world.run_system_once(grant_extra_life).unwrap();
+        assert_eq!(**world.resource::<Lives>(), MAX_LIVES);
+    }
+
+    #[test]
+    fn a_spare_life_brings_the_rug_back() {
+        let mut world = World::new();
+        world.init_resource::<Events<HealthChanged>>();
+        world.insert_resource(Lives(1));
+        let player = world
+            .spawn((
+                Player,
+                Health {
+                    current: 0,
+                    max: MAX_HEALTH,
+                    partial: 0.0,
+                },
+            ))
+            .id();
+
+        world.run_system_once(spend_lives).unwrap();
+
+        assert_eq!(world.get::<Health>(player).unwrap().current, MAX_HEALTH);
+        assert!(world.get::<Invulnerable>(player).is_some());
+        assert_eq!(**world.resource::<Lives>(), 0);
+    }
+}
@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+
+use crate::RunEntity;
+
+const CRITICAL_FLASH_COLOR: Color = Color::srgba(1.0, 0.0, 0.0, 0.45);
+const CRITICAL_FLASH_DURATION: f32 = 0.25;
+
+/// Plugin for one-off full screen flashes
+pub struct FlashPlugin;
+
+impl Plugin for FlashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, fade_screen_flash);
+    }
+}
+
+/// Full screen overlay that fades out from `color` over `timer`, then
+/// removes itself
+#[derive(Component)]
+pub struct ScreenFlash {
+    timer: Timer,
+    color: Color,
+}
+
+/// Flash the screen red, for the hit that leaves the player on their last
+/// hit point
+pub fn spawn_critical_flash(commands: &mut Commands) {
+    spawn_screen_flash(commands, CRITICAL_FLASH_COLOR, CRITICAL_FLASH_DURATION);
+}
+
+pub fn spawn_screen_flash(commands: &mut Commands, color: Color, duration: f32) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(color),
+        GlobalZIndex(i32::MAX),
+        ScreenFlash {
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+            color,
+        },
+        RunEntity,
+    ));
+}
+
+fn fade_screen_flash(
+    mut commands: Commands,
+    mut flashes: Query<(Entity, &mut ScreenFlash, &mut BackgroundColor)>,
+    time: Res<Time>,
+) {
+    for (entity, mut flash, mut background) in &mut flashes {
+        if flash.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let alpha = flash.color.alpha() * flash.timer.fraction_remaining();
+        background.0 = flash.color.with_alpha(alpha);
+    }
+}
@@ -0,0 +1,106 @@
+use bevy::{prelude::*, ui::ComputedNode};
+
+use crate::{settings::GameSettings, CollisionEvent, MainCamera, RunEntity, ScoreboardUi};
+
+const FLYING_ICON_SIZE: f32 = 16.0;
+const FLYING_DURATION: f32 = 0.5;
+
+/// Plugin for the gem icons that fly from a collected gem to the scoreboard
+pub struct FlyingScorePlugin;
+
+impl Plugin for FlyingScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScoreInFlight>()
+            .add_systems(Update, (spawn_flying_scores, fly_scores).chain());
+    }
+}
+
+/// Points already in `Score` whose icon hasn't reached the scoreboard yet
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct ScoreInFlight(usize);
+
+/// Icon moving from `start` to `end` in UI coordinates, adding `value` to
+/// the shown score once it arrives
+#[derive(Component)]
+struct FlyingScore {
+    timer: Timer,
+    start: Vec2,
+    end: Vec2,
+    value: usize,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_flying_scores(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    mut in_flight: ResMut<ScoreInFlight>,
+    flying: Query<(), With<FlyingScore>>,
+    settings: Res<GameSettings>,
+    asset_server: Res<AssetServer>,
+    ui_scale: Res<UiScale>,
+    camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    scoreboard: Single<(&GlobalTransform, &ComputedNode), With<ScoreboardUi>>,
+) {
+    let (camera, camera_transform) = *camera;
+    let (scoreboard_transform, scoreboard_node) = *scoreboard;
+    // UI transforms are in physical pixels, while `Val::Px` is in logical
+    // pixels before the UI scale
+    let end = scoreboard_transform.translation().truncate()
+        * scoreboard_node.inverse_scale_factor()
+        / ui_scale.0;
+
+    let mut flying_count = flying.iter().count();
+    let max_flying = settings.graphics_quality.max_flying_scores();
+
+    for collision in collisions.read() {
+        if max_flying.is_some_and(|max| flying_count >= max) {
+            continue;
+        }
+        flying_count += 1;
+
+        let value = collision.points;
+        let Ok(start) = camera.world_to_viewport(camera_transform, collision.position.extend(0.0))
+        else {
+            continue;
+        };
+
+        **in_flight += value;
+        commands.spawn((
+            ImageNode::new(asset_server.load("sprites/gem.png")).with_color(collision.kind.color()),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Px(FLYING_ICON_SIZE),
+                height: Val::Px(FLYING_ICON_SIZE),
+                ..default()
+            },
+            FlyingScore {
+                timer: Timer::from_seconds(FLYING_DURATION, TimerMode::Once),
+                start: start / ui_scale.0,
+                end,
+                value,
+            },
+            RunEntity,
+        ));
+    }
+}
+
+fn fly_scores(
+    mut commands: Commands,
+    mut in_flight: ResMut<ScoreInFlight>,
+    mut flying: Query<(Entity, &mut FlyingScore, &mut Node)>,
+    time: Res<Time>,
+) {
+    for (entity, mut flying_score, mut node) in &mut flying {
+        if flying_score.timer.tick(time.delta()).finished() {
+            **in_flight = in_flight.saturating_sub(flying_score.value);
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        // ease out, so the icon leaves the gem quickly and settles in
+        let t = 1.0 - (1.0 - flying_score.timer.fraction()).powi(2);
+        let position = flying_score.start.lerp(flying_score.end, t) - FLYING_ICON_SIZE / 2.0;
+        node.left = Val::Px(position.x);
+        node.top = Val::Px(position.y);
+    }
+}
@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+
+use crate::{collect_gems, move_player, setup, GameState, GameplaySet, HudText, SideHud};
+
+/// Seconds the scroll stays frozen after collecting a freeze gem
+pub const SCROLL_FREEZE_DURATION: f32 = 3.0;
+
+const FREEZE_GEM_COLOR: Color = Color::srgb(0.6, 0.9, 1.0);
+
+/// Plugin for the rare gems that stop the forced scroll for a moment, so
+/// players can sweep up a cluster. Moving up and down still works.
+pub struct FreezePlugin;
+
+impl Plugin for FreezePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScrollFreeze>()
+            .add_event::<FreezeCollected>()
+            .add_systems(Startup, spawn_freeze_ui.after(setup))
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_scroll_freeze
+                        .before(move_player)
+                        .in_set(GameplaySet::Movement),
+                    freeze_scroll
+                        .after(collect_gems)
+                        .in_set(GameplaySet::Collision),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    tint_freeze_gems,
+                    update_freeze_ui.run_if(in_state(GameState::Playing)),
+                ),
+            );
+    }
+}
+
+/// A gem that freezes the scroll for `SCROLL_FREEZE_DURATION` when collected
+#[derive(Component)]
+pub struct FreezeGem;
+
+/// A freeze gem was collected
+#[derive(Event)]
+pub struct FreezeCollected;
+
+/// Time left with the scroll frozen. The default timer has no time on it,
+/// so the scroll isn't frozen.
+#[derive(Resource, Default)]
+pub struct ScrollFreeze {
+    pub timer: Timer,
+}
+
+impl ScrollFreeze {
+    pub fn is_active(&self) -> bool {
+        self.timer.remaining_secs() > 0.0
+    }
+}
+
+#[derive(Component)]
+struct FreezeUi;
+
+fn tick_scroll_freeze(mut freeze: ResMut<ScrollFreeze>, time: Res<Time>) {
+    if freeze.is_active() {
+        freeze.timer.tick(time.delta());
+    }
+}
+
+/// Start the freeze over, rather than stacking, when another gem is collected
+fn freeze_scroll(mut events: EventReader<FreezeCollected>, mut freeze: ResMut<ScrollFreeze>) {
+    if events.read().count() > 0 {
+        freeze.timer = Timer::from_seconds(SCROLL_FREEZE_DURATION, TimerMode::Once);
+    }
+}
+
+fn tint_freeze_gems(mut gems: Query<&mut Sprite, Added<FreezeGem>>) {
+    for mut sprite in &mut gems {
+        sprite.color = FREEZE_GEM_COLOR;
+    }
+}
+
+fn spawn_freeze_ui(mut commands: Commands, side_hud: Single<Entity, With<SideHud>>) {
+    commands.entity(*side_hud).with_child((
+        Text::default(),
+        TextFont::default(),
+        HudText(1.0),
+        TextColor(FREEZE_GEM_COLOR),
+        Visibility::Hidden,
+        FreezeUi,
+    ));
+}
+
+fn update_freeze_ui(
+    freeze: Res<ScrollFreeze>,
+    mut ui: Single<(&mut Text, &mut Visibility), With<FreezeUi>>,
+) {
+    let (ref mut text, ref mut visibility) = *ui;
+    if !freeze.is_active() {
+        **visibility = Visibility::Hidden;
+        return;
+    }
+
+    **visibility = Visibility::Inherited;
+    text.0 = format!("Scroll frozen: {:.1}s", freeze.timer.remaining_secs());
+}
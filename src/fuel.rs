@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+
+use crate::{
+    collect_gems,
+    settings::{DeathCondition, GameSettings},
+    setup, CollisionEvent, GameState, GameplaySet, HudText, SideHud, STAMINA_BAR_BACKGROUND,
+    TEXT_COLOR,
+};
+
+const MAX_FUEL: f32 = 100.0;
+
+const FUEL_COLOR: Color = Color::srgb(0.4, 0.7, 1.0);
+/// Fuel bar color once it gets low
+const LOW_FUEL_COLOR: Color = Color::srgb(1.0, 0.4, 0.3);
+/// Fraction of the tank below which the bar turns to `LOW_FUEL_COLOR`
+const LOW_FUEL: f32 = 0.25;
+const FUEL_BAR_SIZE: Vec2 = Vec2::new(150.0, 10.0);
+
+/// Plugin for the rug's fuel, which burns down over a run and is topped up
+/// by collecting gems. Only used with `DeathCondition::Fuel`, which ends the
+/// run when the tank runs dry.
+pub struct FuelPlugin;
+
+impl Plugin for FuelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Fuel>()
+            .add_systems(Startup, spawn_fuel_ui.after(setup))
+            .add_systems(
+                FixedUpdate,
+                (
+                    burn_fuel.in_set(GameplaySet::Movement),
+                    refuel.after(collect_gems).in_set(GameplaySet::Collision),
+                )
+                    .run_if(uses_fuel),
+            )
+            .add_systems(Update, update_fuel_ui.run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// Fuel left in the tank, from 0 to `MAX_FUEL`
+#[derive(Resource, Deref, DerefMut)]
+pub struct Fuel(f32);
+
+impl Default for Fuel {
+    fn default() -> Self {
+        Fuel(MAX_FUEL)
+    }
+}
+
+impl Fuel {
+    pub fn is_empty(&self) -> bool {
+        self.0 <= 0.0
+    }
+}
+
+/// Fuel bar, shown only while fuel is used
+#[derive(Component)]
+struct FuelRow;
+
+#[derive(Component)]
+struct FuelUi;
+
+fn uses_fuel(settings: Res<GameSettings>) -> bool {
+    settings.death_condition == DeathCondition::Fuel
+}
+
+fn burn_fuel(mut fuel: ResMut<Fuel>, settings: Res<GameSettings>, time: Res<Time>) {
+    **fuel = (**fuel - settings.fuel_drain_rate * time.delta_secs()).max(0.0);
+}
+
+fn refuel(
+    mut collisions: EventReader<CollisionEvent>,
+    mut fuel: ResMut<Fuel>,
+    settings: Res<GameSettings>,
+) {
+    let gems = collisions.read().count();
+    if gems > 0 {
+        **fuel = (**fuel + gems as f32 * settings.fuel_per_gem).min(MAX_FUEL);
+    }
+}
+
+fn spawn_fuel_ui(mut commands: Commands, side_hud: Single<Entity, With<SideHud>>) {
+    commands.entity(*side_hud).with_children(|p| {
+        p.spawn((
+            Node {
+                column_gap: Val::Px(6.0),
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            Visibility::Hidden,
+            FuelRow,
+        ))
+        .with_children(|row| {
+            row.spawn((
+                Text::new("Fuel"),
+                TextFont::default(),
+                HudText(0.75),
+                TextColor(TEXT_COLOR),
+            ));
+            row.spawn((
+                Node {
+                    width: Val::Px(FUEL_BAR_SIZE.x),
+                    height: Val::Px(FUEL_BAR_SIZE.y),
+                    ..default()
+                },
+                BackgroundColor(STAMINA_BAR_BACKGROUND),
+            ))
+            .with_child((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(FUEL_COLOR),
+                FuelUi,
+            ));
+        });
+    });
+}
+
+fn update_fuel_ui(
+    fuel: Res<Fuel>,
+    settings: Res<GameSettings>,
+    mut row: Single<&mut Visibility, With<FuelRow>>,
+    mut bar: Single<(&mut Node, &mut BackgroundColor), With<FuelUi>>,
+) {
+    if settings.is_changed() {
+        **row = if settings.death_condition == DeathCondition::Fuel {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+    if !fuel.is_changed() {
+        return;
+    }
+
+    let (ref mut node, ref mut color) = *bar;
+    let fraction = **fuel / MAX_FUEL;
+    node.width = Val::Percent(fraction * 100.0);
+    color.0 = if fraction < LOW_FUEL {
+        LOW_FUEL_COLOR
+    } else {
+        FUEL_COLOR
+    };
+}
@@ -0,0 +1,345 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    settings::GameSettings, spawn_table::SpawnTable, GameState, GameplaySet, HudText, GEM_SIZE,
+    SCOREBOARD_TEXT_PADDING, TEXT_COLOR,
+};
+
+/// Legend text size, relative to the HUD font size
+const LEGEND_TEXT_SCALE: f32 = 0.45;
+const LEGEND_ICON_SIZE: Val = Val::Px(16.0);
+const LEGEND_BACKGROUND: Color = Color::srgba(1.0, 1.0, 1.0, 0.33);
+
+/// Gem rotation in radians per second
+const GEM_SPIN_SPEED: f32 = 1.5;
+/// How far gems bob up and down around their position
+const GEM_BOB_AMPLITUDE: f32 = 4.0;
+/// Bob phase advance in radians per second
+const GEM_BOB_SPEED: f32 = 3.0;
+/// How long a new gem takes to pop in to full size
+const GEM_SPAWN_ANIM_DURATION: f32 = 0.25;
+/// How far past full size the pop in goes before settling back
+const GEM_SPAWN_OVERSHOOT: f32 = 1.7;
+
+/// Seconds a gem has to be on screen to charge up fully
+const GEM_CHARGE_TIME: f32 = 4.0;
+/// Extra value of a fully charged gem, as a multiple of its normal value
+const MAX_CHARGE_BONUS: f32 = 1.0;
+const CHARGE_GLOW_COLOR: Color = Color::srgb(1.0, 0.95, 0.6);
+/// Opacity of the glow around a fully charged gem
+const MAX_CHARGE_GLOW_ALPHA: f32 = 0.6;
+/// Size of the glow relative to the gem
+const CHARGE_GLOW_SCALE: f32 = 1.8;
+
+/// Plugin for the gem legend panel, charging gems up and the gems' idle
+/// animation
+pub struct GemsPlugin;
+
+impl Plugin for GemsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_gem_legend)
+            .add_systems(Update, (toggle_gem_legend, update_gem_legend).chain())
+            .add_systems(FixedUpdate, charge_gems.in_set(GameplaySet::Movement))
+            .add_systems(Update, (spawn_charge_glows, update_charge_glows).chain())
+            // Cosmetic only, so it runs every frame rather than on the fixed
+            // tick and stays smooth on high refresh rate screens
+            .add_systems(
+                Update,
+                (spin_and_bob_gems, animate_gem_spawns)
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(|settings: Res<GameSettings>| settings.graphics_quality.animate_gems()),
+            );
+    }
+}
+
+/// The different gems that can be collected, and what they are worth
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GemKind {
+    Common,
+    Rare,
+    Precious,
+    /// Looks like a common gem, but hurts instead of scoring
+    Mimic,
+}
+
+impl GemKind {
+    pub const ALL: [GemKind; 4] = [
+        GemKind::Common,
+        GemKind::Rare,
+        GemKind::Precious,
+        GemKind::Mimic,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GemKind::Common => "Common",
+            GemKind::Rare => "Rare",
+            GemKind::Precious => "Precious",
+            GemKind::Mimic => "Mimic",
+        }
+    }
+
+    /// Points awarded for collecting the gem
+    pub fn value(self) -> usize {
+        match self {
+            GemKind::Common => 1,
+            GemKind::Rare => 5,
+            GemKind::Precious => 20,
+            GemKind::Mimic => 0,
+        }
+    }
+
+    /// Tint applied to the gem sprite
+    pub fn color(self) -> Color {
+        match self {
+            GemKind::Common | GemKind::Mimic => Color::WHITE,
+            GemKind::Rare => Color::srgb(0.5, 1.0, 0.5),
+            GemKind::Precious => Color::srgb(1.0, 0.85, 0.3),
+        }
+    }
+}
+
+/// Points a gem is worth before the distance, charge and double points
+/// bonuses. Usually its kind's value.
+#[derive(Component, Deref, Clone, Copy)]
+pub struct GemValue(pub usize);
+
+/// Idle bobbing of a gem, tracked as an offset from wherever gameplay has
+/// moved it so it never fights the pull towards the player
+#[derive(Component)]
+pub struct GemBob {
+    phase: f32,
+    offset: f32,
+}
+
+impl GemBob {
+    pub fn new(phase: f32) -> Self {
+        GemBob { phase, offset: 0.0 }
+    }
+}
+
+/// Pop in of a freshly spawned gem, scaling its sprite up to `size` with a
+/// small overshoot. Only the sprite grows; the gem can be collected straight
+/// away.
+#[derive(Component)]
+pub struct SpawnAnim {
+    timer: Timer,
+    size: Vec2,
+}
+
+impl SpawnAnim {
+    pub fn new(size: Vec2) -> Self {
+        SpawnAnim {
+            timer: Timer::from_seconds(GEM_SPAWN_ANIM_DURATION, TimerMode::Once),
+            size,
+        }
+    }
+}
+
+/// Gems left on screen charge up and become worth more, up to
+/// `MAX_CHARGE_BONUS` extra once the timer finishes
+#[derive(Component)]
+pub struct Charge {
+    timer: Timer,
+}
+
+impl Default for Charge {
+    fn default() -> Self {
+        Charge {
+            timer: Timer::from_seconds(GEM_CHARGE_TIME, TimerMode::Once),
+        }
+    }
+}
+
+impl Charge {
+    /// Multiplier on the gem's value
+    pub fn multiplier(&self) -> f32 {
+        1.0 + MAX_CHARGE_BONUS * self.timer.fraction()
+    }
+}
+
+/// Glow drawn behind a gem, brighter the more it is charged
+#[derive(Component)]
+struct ChargeGlow;
+
+#[derive(Component)]
+struct GemLegendUi;
+
+/// Build a legend row for every gem kind so the panel stays in sync with
+/// their definitions
+fn spawn_gem_legend(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    table: Res<SpawnTable>,
+) {
+    let icon = asset_server.load("sprites/gem.png");
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: SCOREBOARD_TEXT_PADDING,
+                right: SCOREBOARD_TEXT_PADDING,
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            BackgroundColor(LEGEND_BACKGROUND),
+            GemLegendUi,
+        ))
+        .with_children(|p| {
+            for kind in GemKind::ALL {
+                p.spawn(Node {
+                    column_gap: Val::Px(6.0),
+                    align_items: AlignItems::Center,
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn((
+                        ImageNode::new(icon.clone()).with_color(kind.color()),
+                        Node {
+                            width: LEGEND_ICON_SIZE,
+                            height: LEGEND_ICON_SIZE,
+                            ..default()
+                        },
+                    ));
+                    row.spawn((
+                        Text::new(match kind {
+                            // looks just like a common gem, so say what it does
+                            GemKind::Mimic => format!("{}: hurts", kind.name()),
+                            _ => format!("{}: {}", kind.name(), table.gem_value(kind)),
+                        }),
+                        TextFont::default(),
+                        HudText(LEGEND_TEXT_SCALE),
+                        TextColor(TEXT_COLOR),
+                    ));
+                });
+            }
+        });
+}
+
+fn toggle_gem_legend(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<GameSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        settings.show_gem_legend = !settings.show_gem_legend;
+        settings.save();
+    }
+}
+
+fn update_gem_legend(
+    settings: Res<GameSettings>,
+    mut legend: Single<&mut Visibility, With<GemLegendUi>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    **legend = if settings.show_gem_legend {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+}
+
+/// Spin and bob every gem. Both advance by `time.delta_secs()`, so the
+/// animation runs at the same speed whatever the frame rate.
+fn spin_and_bob_gems(mut gems: Query<(&mut Transform, &mut GemBob)>, time: Res<Time>) {
+    let delta = time.delta_secs();
+
+    for (mut transform, mut bob) in &mut gems {
+        transform.rotate_z(GEM_SPIN_SPEED * delta);
+
+        bob.phase += GEM_BOB_SPEED * delta;
+        let offset = bob.phase.sin() * GEM_BOB_AMPLITUDE;
+        transform.translation.y += offset - bob.offset;
+        bob.offset = offset;
+    }
+}
+
+/// Charge every gem that was on screen last frame. Mimics charge too, so the
+/// glow doesn't give them away.
+fn charge_gems(mut gems: Query<(&mut Charge, &ViewVisibility)>, time: Res<Time>) {
+    for (mut charge, visibility) in &mut gems {
+        if visibility.get() {
+            charge.timer.tick(time.delta());
+        }
+    }
+}
+
+fn spawn_charge_glows(
+    mut commands: Commands,
+    gems: Query<Entity, Added<Charge>>,
+    asset_server: Res<AssetServer>,
+) {
+    for gem in &gems {
+        commands.entity(gem).with_child((
+            Sprite {
+                image: asset_server.load("sprites/gem.png"),
+                custom_size: Some(Vec2::splat(GEM_SIZE * CHARGE_GLOW_SCALE)),
+                color: CHARGE_GLOW_COLOR.with_alpha(0.0),
+                ..default()
+            },
+            // just behind the gem
+            Transform::from_xyz(0.0, 0.0, -0.1),
+            ChargeGlow,
+        ));
+    }
+}
+
+fn update_charge_glows(
+    gems: Query<(&Charge, &Children)>,
+    mut glows: Query<&mut Sprite, With<ChargeGlow>>,
+) {
+    for (charge, children) in &gems {
+        let alpha = MAX_CHARGE_GLOW_ALPHA * charge.timer.fraction();
+        let mut iter = glows.iter_many_mut(children);
+        while let Some(mut glow) = iter.fetch_next() {
+            glow.color.set_alpha(alpha);
+        }
+    }
+}
+
+fn animate_gem_spawns(
+    mut commands: Commands,
+    mut gems: Query<(Entity, &mut Sprite, &mut SpawnAnim)>,
+    time: Res<Time>,
+) {
+    for (entity, mut sprite, mut anim) in &mut gems {
+        anim.timer.tick(time.delta());
+
+        // ease out with an overshoot, ending at exactly 1
+        let t = anim.timer.fraction() - 1.0;
+        let scale = 1.0 + (GEM_SPAWN_OVERSHOOT + 1.0) * t.powi(3) + GEM_SPAWN_OVERSHOOT * t.powi(2);
+        sprite.custom_size = Some(anim.size * scale);
+
+        if anim.timer.finished() {
+            commands.entity(entity).remove::<SpawnAnim>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legend_lists_every_gem_kind() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .insert_resource(SpawnTable::default())
+            .add_systems(Startup, spawn_gem_legend);
+        app.update();
+
+        let mut texts = app.world_mut().query::<&Text>();
+        let rows: Vec<_> = texts.iter(app.world()).map(|text| text.0.clone()).collect();
+        for kind in GemKind::ALL {
+            assert!(rows.iter().any(|row| row.starts_with(kind.name())));
+        }
+        assert!(rows.contains(&"Mimic: hurts".to_string()));
+    }
+}
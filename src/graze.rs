@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+
+use crate::{
+    collect_gems, settings::GameSettings, setup, GameState, GameplaySet, Gem, HudText, Player,
+    Score, SideHud, GEM_PICKUP_RADIUS,
+};
+
+/// Gems closer than this, but outside the pickup radius, are being grazed
+const GRAZE_RADIUS: f32 = 70.0;
+/// Points for each graze tick
+const GRAZE_POINTS: usize = 1;
+/// Time between two graze ticks on the same gem
+const GRAZE_INTERVAL: f32 = 0.25;
+/// Most graze ticks a single gem can give, so hovering next to one doesn't
+/// farm points
+const MAX_GRAZES_PER_GEM: u32 = 4;
+
+const GRAZE_COLOR: Color = Color::srgb(0.6, 0.4, 1.0);
+
+/// Plugin to award points for flying close past gems
+pub struct GrazePlugin;
+
+impl Plugin for GrazePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GrazeScore>()
+            .add_systems(Startup, spawn_graze_ui.after(setup))
+            .add_systems(
+                FixedUpdate,
+                graze_gems
+                    .after(collect_gems)
+                    .in_set(GameplaySet::Collision),
+            )
+            .add_systems(Update, update_graze_ui.run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// Points from grazing in the current run, already included in `Score`
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct GrazeScore(usize);
+
+/// A gem the player has grazed, limiting how often it gives points
+#[derive(Component)]
+struct Grazed {
+    cooldown: Timer,
+    count: u32,
+}
+
+#[derive(Component)]
+struct GrazeUi;
+
+fn graze_gems(
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    mut graze_score: ResMut<GrazeScore>,
+    players: Query<&Transform, With<Player>>,
+    mut gem_query: Query<(Entity, &Transform, Option<&mut Grazed>), With<Gem>>,
+    settings: Res<GameSettings>,
+    time: Res<Time>,
+) {
+    // the band moves out along with the assisted pickup radius
+    let scale = settings.assist_mode.pickup_radius_scale();
+    let band = GEM_PICKUP_RADIUS * scale..GRAZE_RADIUS * scale;
+
+    for (gem_entity, transform, grazed) in &mut gem_query {
+        // measured to the nearest rug, so two players can't double up
+        let gem_pos = transform.translation.truncate();
+        let distance = players
+            .iter()
+            .map(|player| player.translation.truncate().distance(gem_pos))
+            .fold(f32::INFINITY, f32::min);
+        let in_band = band.contains(&distance);
+
+        match grazed {
+            Some(mut grazed) => {
+                grazed.cooldown.tick(time.delta());
+                if !in_band || grazed.count >= MAX_GRAZES_PER_GEM || !grazed.cooldown.finished() {
+                    continue;
+                }
+                grazed.count += 1;
+                grazed.cooldown.reset();
+            }
+            None if in_band => {
+                commands.entity(gem_entity).insert(Grazed {
+                    cooldown: Timer::from_seconds(GRAZE_INTERVAL, TimerMode::Once),
+                    count: 1,
+                });
+            }
+            None => continue,
+        }
+
+        **score += GRAZE_POINTS;
+        **graze_score += GRAZE_POINTS;
+    }
+}
+
+fn spawn_graze_ui(mut commands: Commands, side_hud: Single<Entity, With<SideHud>>) {
+    commands.entity(*side_hud).with_child((
+        Text::default(),
+        TextFont::default(),
+        HudText(0.75),
+        TextColor(GRAZE_COLOR),
+        GrazeUi,
+    ));
+}
+
+fn update_graze_ui(graze_score: Res<GrazeScore>, mut text: Single<&mut Text, With<GrazeUi>>) {
+    if !graze_score.is_changed() {
+        return;
+    }
+
+    text.0 = if **graze_score > 0 {
+        format!("Graze +{}", **graze_score)
+    } else {
+        String::new()
+    };
+}
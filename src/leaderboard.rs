@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+
+use crate::{
+    daily, format_score,
+    save::{LeaderboardEntry, SaveData},
+    settings::{AssistMode, GameSettings},
+    GameMode, GameState, HudText, RunFlags, Score, SideHud, TEXT_COLOR,
+};
+
+/// Leaderboard text size, relative to the HUD font size
+const LEADERBOARD_TEXT_SCALE: f32 = 0.6;
+const HIGHLIGHT_COLOR: Color = Color::srgb(1.0, 0.5, 0.5);
+
+/// Plugin to keep and show the local top scores
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::MainMenu), show_menu_leaderboard)
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                (record_score, show_results_leaderboard).chain(),
+            );
+    }
+}
+
+/// Rank of the finished run on the leaderboard, if it made it
+#[derive(Resource)]
+struct LatestRank(Option<usize>);
+
+fn record_score(
+    mut commands: Commands,
+    score: Res<Score>,
+    settings: Res<GameSettings>,
+    mode: Res<GameMode>,
+    flags: Res<RunFlags>,
+    mut save: ResMut<SaveData>,
+) {
+    // time attacks are ranked by their time instead
+    if settings.assist_mode == AssistMode::On
+        || flags.cheated
+        || matches!(*mode, GameMode::TimeAttack(_))
+    {
+        commands.insert_resource(LatestRank(None));
+        return;
+    }
+
+    let rank = save.insert_score(LeaderboardEntry::new(**score, daily::today().to_string()));
+    if rank.is_some() {
+        save.save();
+    }
+    commands.insert_resource(LatestRank(rank));
+}
+
+fn show_menu_leaderboard(
+    mut commands: Commands,
+    save: Res<SaveData>,
+    settings: Res<GameSettings>,
+    side_hud: Single<Entity, With<SideHud>>,
+) {
+    spawn_leaderboard(
+        &mut commands,
+        *side_hud,
+        &save,
+        &settings,
+        None,
+        GameState::MainMenu,
+    );
+}
+
+fn show_results_leaderboard(
+    mut commands: Commands,
+    save: Res<SaveData>,
+    settings: Res<GameSettings>,
+    rank: Res<LatestRank>,
+    side_hud: Single<Entity, With<SideHud>>,
+) {
+    spawn_leaderboard(
+        &mut commands,
+        *side_hud,
+        &save,
+        &settings,
+        rank.0,
+        GameState::GameOver,
+    );
+}
+
+/// Spawn one text row per leaderboard entry into the side HUD, highlighting
+/// `highlight`
+fn spawn_leaderboard(
+    commands: &mut Commands,
+    side_hud: Entity,
+    save: &SaveData,
+    settings: &GameSettings,
+    highlight: Option<usize>,
+    state: GameState,
+) {
+    let leaderboard = commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            StateScoped(state),
+        ))
+        .with_children(|p| {
+            p.spawn((
+                Text::new("Top scores"),
+                TextFont::default(),
+                HudText(LEADERBOARD_TEXT_SCALE * 1.25),
+                TextColor(TEXT_COLOR),
+            ));
+
+            for (i, entry) in save.leaderboard.iter().enumerate() {
+                let color = if highlight == Some(i) {
+                    HIGHLIGHT_COLOR
+                } else {
+                    TEXT_COLOR
+                };
+                p.spawn((
+                    Text::new(format!(
+                        "{:>2}. {:>7}  {}",
+                        i + 1,
+                        format_score(entry.score, settings.score_format),
+                        entry.date
+                    )),
+                    TextFont::default(),
+                    HudText(LEADERBOARD_TEXT_SCALE),
+                    TextColor(color),
+                ));
+            }
+        })
+        .id();
+    commands.entity(side_hud).add_child(leaderboard);
+}
@@ -1,8 +1,74 @@
-use bevy::prelude::*;
+use std::time::{Duration, Instant};
 
+use bevy::{
+    log::{LogPlugin, DEFAULT_FILTER},
+    prelude::*,
+    state::state::StateTransitionEvent,
+    window::PrimaryWindow,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+use beats::BeatClock;
+use combo::Combo;
+use double_points::{DoublePoints, DoublePointsCollected, ScoreMultiplier};
+use extra_life::{ExtraLife, ExtraLifeCollected, Lives};
+use flying_score::ScoreInFlight;
+use freeze::{FreezeCollected, FreezeGem, ScrollFreeze};
+use fuel::Fuel;
+use gems::{Charge, GemKind, GemValue};
+use graze::GrazeScore;
+use milestones::{GemMilestone, GEM_MILESTONE_INTERVAL};
+use navigation::MenuActivated;
+use obstacles::{Invulnerable, Obstacle, RESPAWN_SAFE_DISTANCE};
+use pickup_trail::PickupTrail;
+use rewind::{RewindCollected, RewindGem, StateHistory};
+use save::SaveData;
+use settings::{
+    AssistMode, DeathCondition, DifficultyCurve, GameSettings, HealthDisplayMode, ScoreFormat,
+    Volumes,
+};
+use spawning::{CollectionRatio, GemStream};
+use split::{SplitGem, SplitGemCollected};
+use streak::NoHitStreak;
+use time_of_day::TimeOfDay;
+
+mod beats;
+mod camera;
+#[cfg(debug_assertions)]
+mod cheats;
+mod collider_debug;
+mod combo;
+mod daily;
+mod double_points;
+mod event_log;
+mod extra_life;
+mod flash;
+mod flying_score;
+mod freeze;
+mod fuel;
+mod gems;
+mod graze;
+mod leaderboard;
+mod menu;
+mod milestones;
+mod mimic;
+mod music;
+mod navigation;
+mod obstacles;
+mod pickup_trail;
+mod replay;
+mod rewind;
+mod save;
+mod settings;
+mod share;
+mod spawn_table;
+mod spawning;
+mod split;
 mod stepping;
+mod streak;
+mod time_attack;
+mod time_of_day;
 
-const SCOREBOARD_FONT_SIZE: f32 = 33.0;
 const SCOREBOARD_TEXT_PADDING: Val = Val::Px(5.0);
 
 const BACKGROUND_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
@@ -12,13 +78,177 @@ const RED_TEXT: Color = Color::srgb(1.0, 0.5, 0.5);
 
 const SCORE_COLOR: Color = Color::srgb(1.0, 0.5, 0.5);
 
+/// Depth each kind of sprite is drawn at; higher is drawn on top. Sprites
+/// that share a layer have no set order between them, so anything that has
+/// to show over something else needs its own layer.
+///
+/// - safe lanes are marked on the ground, behind everything
+/// - telegraphs warn of an obstacle, and are replaced by it on the layer above
+/// - gems show over obstacles, so one sitting next to a wall isn't hidden
+/// - the rugs are always on top
+const SAFE_LANE_Z: f32 = -1.0;
+const TELEGRAPH_Z: f32 = 0.0;
+const OBSTACLE_Z: f32 = 1.0;
+const GEM_Z: f32 = 2.0;
+const PLAYER_Z: f32 = 3.0;
+
 const GEM_SIZE: f32 = 25.;
+/// Distance from the player within which a gem is collected
+const GEM_PICKUP_RADIUS: f32 = 30.0;
 const PLAYER_SIZE: f32 = 100.;
 const MAX_HEALTH: i32 = 3;
+/// Health every rug is revived with by a second wind
+const SECOND_WIND_HEALTH: i32 = 1;
+const SECOND_WIND_FLASH_COLOR: Color = Color::srgba(1.0, 0.85, 0.3, 0.5);
+const SECOND_WIND_FLASH_DURATION: f32 = 0.5;
+/// Size of the box used for the player's collisions; smaller than the sprite
+/// so glancing blows don't count
+const PLAYER_HITBOX: Vec2 = Vec2::splat(PLAYER_SIZE * 0.6);
+
+const HEALTH_BAR_COLOR: Color = Color::srgb(0.5, 1.0, 0.5);
+const HEALTH_BAR_SIZE: Vec2 = Vec2::new(150.0, 14.0);
+/// How quickly the health bar closes the gap to the actual health, as a
+/// fraction per second
+const HEALTH_BAR_EASING: f32 = 6.0;
+/// Size of a heart in `HealthDisplayMode::Hearts`, and the gap between them
+const HEART_SIZE: f32 = 14.0;
+const HEART_GAP: f32 = 4.0;
+const LOST_HEART_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.2);
+
+/// Seconds after dying before the results screen takes input, so keys
+/// still held from playing don't restart straight away
+const GAME_OVER_INPUT_DELAY: f32 = 1.0;
+
+/// Extra gem value per unit of distance flown, so later gems are worth more
+const DISTANCE_BONUS_SCALE: f32 = 0.00005;
+/// Highest multiplier the distance can put on a gem's value
+const MAX_DISTANCE_BONUS: f32 = 3.0;
+
+/// How long the scoreboard pulses when the score goes up
+const SCORE_PULSE_DURATION: f32 = 0.15;
+/// Extra scale of the scoreboard at the height of a pulse
+const SCORE_PULSE_SCALE: f32 = 0.12;
+/// Scores from this up are shortened by `ScoreFormat::Abbreviated`
+const SCORE_ABBREVIATION_THRESHOLD: usize = 10_000;
+
+const STAMINA_COLOR: Color = Color::srgb(1.0, 0.8, 0.3);
+const STAMINA_BAR_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.2);
+const STAMINA_BAR_SIZE: Vec2 = Vec2::new(150.0, 10.0);
+
+const MAX_STAMINA: f32 = 100.0;
+/// Stamina regained per second while not dashing
+const STAMINA_REGEN_RATE: f32 = 20.0;
+/// Stamina spent to start a dash
+const DASH_STAMINA_COST: f32 = 35.0;
+const DASH_DURATION: f32 = 0.25;
+const DASH_SPEED_MULTIPLIER: f32 = 2.5;
+/// How quickly the rug's speed follows the brake being pressed or let go, as
+/// a fraction per second
+const BRAKE_EASING: f32 = 6.0;
+
+/// Largest allowed analog deadzone, so some stick travel is always left
+const MAX_DEADZONE: f32 = 0.9;
+
+/// How quickly the rug closes the gap to the cursor height while the mouse
+/// button is held, as a fraction per second
+const MOUSE_FOLLOW_RATE: f32 = 8.0;
+
+/// Keys for each player; the second one only joins in co-op. The brake keys
+/// are in the settings.
+const PLAYER_CONTROLS: [PlayerControls; 2] = [
+    PlayerControls {
+        up: KeyCode::ArrowUp,
+        down: KeyCode::ArrowDown,
+        dash: KeyCode::ShiftLeft,
+    },
+    PlayerControls {
+        up: KeyCode::KeyW,
+        down: KeyCode::KeyS,
+        dash: KeyCode::KeyE,
+    },
+];
+/// Tint telling the second player's rug apart
+const SECOND_PLAYER_COLOR: Color = Color::srgb(0.7, 0.8, 1.0);
+/// Vertical distance between the rugs at the start of a co-op run
+const COOP_START_SPACING: f32 = 150.0;
+
+/// Horizontal speed of the rug when not dashing
+const SCROLL_SPEED: f32 = 300.0;
+
+/// The camera never looks further ahead than this many times the lead at
+/// the normal scroll speed
+const CAMERA_MAX_LEAD_RATIO: f32 = 2.0;
+/// How quickly the look-ahead catches up with a change in speed, as a
+/// fraction per second
+const CAMERA_LEAD_EASING: f32 = 3.0;
+/// Once the look-ahead falls this far behind its target, such as after a
+/// hitch or a dash, it eases at `CAMERA_CATCH_UP_EASING` instead
+const CAMERA_CATCH_UP_START: f32 = 150.0;
+/// Distance from the target at which the catch-up ends and the normal
+/// easing takes over again
+const CAMERA_CATCH_UP_STOP: f32 = 10.0;
+const CAMERA_CATCH_UP_EASING: f32 = 12.0;
+/// Furthest the camera is nudged up or down towards upcoming obstacles
+const DANGER_NUDGE: f32 = 40.0;
+/// Obstacles up to this far ahead of the camera center pull the camera
+/// towards them, more strongly the closer they are
+const DANGER_NUDGE_RANGE: f32 = 900.0;
+/// How quickly the nudge follows the obstacles, as a fraction per second.
+/// Kept slow so the camera drifts rather than jerks.
+const DANGER_NUDGE_EASING: f32 = 1.5;
+
+/// Distance between the listener's ears for panned pickup sounds. Sounds
+/// are placed at most half of this to either side, where the far ear gets a
+/// quarter of the volume.
+const PICKUP_EAR_GAP: f32 = 2.0;
+
+/// Gems within this distance, directly ahead of the moving rug, get pulled in
+const GEM_CONE_RANGE: f32 = 250.0;
+/// Half of the pickup cone's opening angle (15 degrees)
+const GEM_CONE_HALF_ANGLE: f32 = std::f32::consts::PI / 12.0;
+/// Speed at which gems inside the cone drift towards the player
+const GEM_CONE_PULL: f32 = 120.0;
+/// Gems this close to a pulled gem get pulled along with it
+const GEM_CHAIN_RADIUS: f32 = 60.0;
+/// Most links of gems pulling their neighbours, counting from a gem in the
+/// cone, so a dense cluster can't drag in the whole screen
+const MAX_GEM_CHAIN_DEPTH: usize = 3;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(log_plugin()))
+        .add_plugins(save::SavePlugin)
+        .add_plugins(settings::SettingsPlugin)
+        .add_plugins(spawn_table::SpawnTablePlugin)
+        .add_plugins(gems::GemsPlugin)
+        .add_plugins(camera::CameraPlugin)
+        .add_plugins(navigation::NavigationPlugin)
+        .add_plugins(menu::MenuPlugin)
+        .add_plugins(daily::DailyPlugin)
+        .add_plugins(leaderboard::LeaderboardPlugin)
+        .add_plugins(replay::ReplayPlugin)
+        .add_plugins(milestones::MilestonesPlugin)
+        .add_plugins(music::MusicPlugin)
+        .add_plugins(combo::ComboPlugin)
+        .add_plugins(graze::GrazePlugin)
+        .add_plugins(spawning::SpawningPlugin)
+        .add_plugins(beats::BeatsPlugin)
+        .add_plugins(split::SplitPlugin)
+        .add_plugins(rewind::RewindPlugin)
+        .add_plugins(freeze::FreezePlugin)
+        .add_plugins(double_points::DoublePointsPlugin)
+        .add_plugins(extra_life::ExtraLifePlugin)
+        .add_plugins(mimic::MimicPlugin)
+        .add_plugins(obstacles::ObstaclesPlugin)
+        .add_plugins(flash::FlashPlugin)
+        .add_plugins(event_log::EventLogPlugin)
+        .add_plugins(collider_debug::ColliderDebugPlugin)
+        .add_plugins(flying_score::FlyingScorePlugin)
+        .add_plugins(fuel::FuelPlugin)
+        .add_plugins(pickup_trail::PickupTrailPlugin)
+        .add_plugins(streak::StreakPlugin)
+        .add_plugins(time_of_day::TimeOfDayPlugin)
+        .add_plugins(time_attack::TimeAttackPlugin)
         .add_plugins(
             stepping::SteppingPlugin::default()
                 .add_schedule(Update)
@@ -26,37 +256,161 @@ fn main() {
                 .at(Val::Percent(35.0), Val::Percent(50.0)),
         )
         .insert_resource(Score(0))
+        .init_resource::<Stats>()
+        .init_resource::<RunFlags>()
+        .insert_resource(Difficulty(1.0))
+        .insert_resource(Stamina(MAX_STAMINA))
+        .insert_resource(GameRng::from_entropy())
+        .init_resource::<GameMode>()
+        .init_non_send_resource::<share::SystemClipboard>()
         .insert_resource(ClearColor(BACKGROUND_COLOR))
         .add_event::<CollisionEvent>()
-        .add_systems(Startup, setup)
-        .insert_state(GameState::Playing)
+        .add_event::<HealthChanged>()
+        .add_event::<GameStateChanged>()
+        .add_systems(Startup, (setup, apply_window_settings))
+        .add_systems(Last, limit_frame_rate)
+        .add_systems(Update, (apply_ui_scale, apply_hud_font_size))
+        .add_systems(Update, emit_state_changes)
+        .insert_state(GameState::MainMenu)
+        .enable_state_scoped_entities::<GameState>()
+        // A fresh level is only spawned when there is no run in progress, so
+        // resuming from `Paused` keeps the current one
+        .add_systems(
+            OnEnter(GameState::Playing),
+            spawn_level.run_if(not(any_with_component::<Player>)),
+        )
+        .add_systems(OnEnter(GameState::MainMenu), cleanup_run)
+        // Everything on the game clock holds while paused, including effects
+        // outside the gameplay sets, and fixed ticks don't pile up to catch
+        // up on afterwards
+        .add_systems(OnEnter(GameState::Paused), pause_game_clock)
+        .add_systems(OnExit(GameState::Paused), resume_game_clock)
+        .add_systems(OnExit(GameState::GameOver), (cleanup_run, show_game_over))
+        .configure_sets(FixedUpdate, gameplay_sets())
         // Add our gameplay simulation systems to the fixed timestep schedule
         // which runs at 64 Hz by default
+        .add_systems(FixedUpdate, update_difficulty.in_set(GameplaySet::Spawn))
         .add_systems(
             FixedUpdate,
-            (move_player, follow_player, collect_gems)
+            (
+                dash,
+                move_player,
+                mouse_control,
+                track_distance,
+                follow_player,
+            )
                 // `chain`ing systems together runs them in order
                 .chain()
+                .in_set(GameplaySet::Movement),
+        )
+        .add_systems(
+            FixedUpdate,
+            (pull_gems_in_cone, collect_gems)
+                .chain()
+                .in_set(GameplaySet::Collision),
+        )
+        .add_systems(
+            Update,
+            (
+                (update_scoreboard, pulse_ui).chain(),
+                update_health_ui,
+                (read_health_changes, tween_health_bar).chain(),
+                update_stamina_ui,
+            )
                 .run_if(in_state(GameState::Playing)),
         )
+        .add_systems(Update, cycle_health_display)
         .add_systems(
             Update,
-            (update_scoreboard, update_health_ui).run_if(in_state(GameState::Playing)),
+            check_player_death.run_if(in_state(GameState::Playing)),
         )
-        .add_systems(Update, check_player_death)
-        .add_systems(OnEnter(GameState::GameOver), show_game_over)
-        .run();
+        .add_systems(
+            Update,
+            (reveal_results_menu, handle_game_over_input)
+                .chain()
+                .after(navigation::navigate_menus)
+                .run_if(in_state(GameState::GameOver)),
+        )
+        .add_systems(
+            OnEnter(GameState::GameOver),
+            (show_game_over, record_last_seed, start_game_over_delay),
+        );
+    // cheats are left out of release builds entirely
+    #[cfg(debug_assertions)]
+    app.add_plugins(cheats::CheatsPlugin);
+    app.run();
+}
+
+/// Gameplay events are logged at debug level, which only debug builds show
+/// by default. `RUST_LOG` still overrides this, e.g. `RUST_LOG=magic_rug=debug`
+/// to get them from a release build.
+fn log_plugin() -> LogPlugin {
+    let filter = if cfg!(debug_assertions) {
+        format!("{DEFAULT_FILTER},magic_rug=debug")
+    } else {
+        DEFAULT_FILTER.to_string()
+    };
+    LogPlugin {
+        filter,
+        ..default()
+    }
 }
 
 #[derive(Component)]
 struct Player;
 
+/// Which player a rug belongs to, indexing `PLAYER_CONTROLS`. The first
+/// player also steers with the mouse and gamepads.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct PlayerId(usize);
+
+impl PlayerId {
+    fn controls(self) -> &'static PlayerControls {
+        &PLAYER_CONTROLS[self.0]
+    }
+}
+
+struct PlayerControls {
+    up: KeyCode,
+    down: KeyCode,
+    dash: KeyCode,
+}
+
+/// Text sized as a multiple of the HUD font size setting
+#[derive(Component)]
+struct HudText(f32);
+
+/// The camera that renders the level and the HUD
+#[derive(Component)]
+struct MainCamera;
+
 #[derive(Component)]
 struct Health {
     current: i32,
     max: i32,
+    /// Damage taken that didn't add up to a whole hit point yet
+    partial: f32,
+}
+
+/// Movement over the last tick, in units per second
+#[derive(Component, Default, Deref, DerefMut)]
+struct Velocity(Vec2);
+
+/// Fraction of the full scroll speed the rug is moving at, eased towards
+/// the brake's minimum while it's held and back to 1 once let go
+#[derive(Component, Deref, DerefMut)]
+struct Throttle(f32);
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Throttle(1.0)
+    }
 }
 
+/// Temporary speed boost, removed once the timer runs out
+#[derive(Component, Deref, DerefMut)]
+struct Dashing(Timer);
+
 #[derive(Component)]
 struct Gem;
 
@@ -66,258 +420,1774 @@ struct CollisionSound(Handle<AudioSource>);
 #[derive(Component)]
 struct Collider;
 
-#[derive(Event, Default)]
-struct CollisionEvent;
+/// Marks entities that belong to the current run, so they can be cleaned up
+/// on restart or when returning to the menu
+#[derive(Component)]
+struct RunEntity;
+
+/// A gem was collected
+#[derive(Event)]
+struct CollisionEvent {
+    position: Vec2,
+    kind: GemKind,
+    /// Points scored for it, including the distance bonus
+    points: usize,
+}
+
+/// Sent whenever the player's health changes, including when a run starts
+#[derive(Event)]
+struct HealthChanged {
+    current: i32,
+    max: i32,
+}
 
 #[derive(Resource, Deref, DerefMut)]
 struct Score(usize);
 
+/// One-off things that have happened in the current run
+#[derive(Resource, Default)]
+struct RunFlags {
+    /// The rugs were already revived by a second wind
+    second_wind_used: bool,
+    /// A cheat code was used, so the score isn't ranked
+    cheated: bool,
+}
+
+/// Counters for the current run
+#[derive(Resource, Default)]
+struct Stats {
+    gems_collected: usize,
+    /// Seconds of play so far
+    elapsed: f32,
+    /// How far the furthest rug has flown from the start
+    distance: f32,
+}
+
+impl Stats {
+    /// Multiplier on gem values for having flown this far
+    fn distance_bonus(&self) -> f32 {
+        (1.0 + self.distance * DISTANCE_BONUS_SCALE).min(MAX_DISTANCE_BONUS)
+    }
+}
+
+/// Energy spent on dashing, regenerating over time
+#[derive(Resource, Deref, DerefMut)]
+struct Stamina(f32);
+
+/// Seeded random number generator used for all level placement, so the same
+/// seed always lays out the same run
+#[derive(Resource, Deref, DerefMut)]
+struct GameRng {
+    seed: u64,
+    #[deref]
+    rng: StdRng,
+}
+
+impl GameRng {
+    fn from_seed(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Generator for a fresh run with an unpredictable seed
+    fn from_entropy() -> Self {
+        GameRng::from_seed(rand::random())
+    }
+}
+
+/// Which kind of run is being played
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+enum GameMode {
+    #[default]
+    Endless,
+    /// Everyone plays the same layout, seeded from the current date
+    Daily,
+    /// Replay the layout of a seed entered in the menu
+    Seeded(u64),
+    /// Race to collect `TIME_ATTACK_GEMS`, on a fresh layout or a given seed
+    TimeAttack(Option<u64>),
+}
+
+impl GameMode {
+    /// Random generator for a new run in this mode
+    fn run_rng(self) -> GameRng {
+        match self {
+            GameMode::Endless | GameMode::TimeAttack(None) => GameRng::from_entropy(),
+            GameMode::Daily => GameRng::from_seed(daily::today().seed()),
+            GameMode::Seeded(seed) | GameMode::TimeAttack(Some(seed)) => GameRng::from_seed(seed),
+        }
+    }
+}
+
+/// How hard the current run is; starts at 1 and ramps up as the run goes on
+#[derive(Resource, Deref, DerefMut)]
+struct Difficulty(f32);
+
 // UIs
 #[derive(Component)]
 struct ScoreboardUi;
 
+/// Brief grow and shrink of a UI node, as feedback for a change
+#[derive(Component)]
+struct Pulse {
+    timer: Timer,
+}
+
 #[derive(Component)]
 struct HealthUi;
 
+/// Row holding a heart per hit point, in `HealthDisplayMode::Hearts`
+#[derive(Component)]
+struct HeartsUi;
+
+/// Fill of the health bar, easing towards `target` (a fraction of the max)
+#[derive(Component)]
+struct HealthBarUi {
+    target: f32,
+}
+
+#[derive(Component)]
+struct StaminaUi;
+
 #[derive(Component)]
 struct GameOverUi;
 
+/// Time left before the results screen shows its options
+#[derive(Resource, Deref, DerefMut)]
+struct GameOverDelay(Timer);
+
+/// What a results screen row does when activated
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum ResultsAction {
+    RetrySeed,
+    NewRun,
+    Share,
+    Menu,
+}
+
+impl ResultsAction {
+    const ALL: [ResultsAction; 4] = [
+        ResultsAction::RetrySeed,
+        ResultsAction::NewRun,
+        ResultsAction::Share,
+        ResultsAction::Menu,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ResultsAction::RetrySeed => "R: retry seed",
+            ResultsAction::NewRun => "N: new run",
+            ResultsAction::Share => "C: copy results",
+            ResultsAction::Menu => "M: menu",
+        }
+    }
+
+    fn hotkey(self) -> KeyCode {
+        match self {
+            ResultsAction::RetrySeed => KeyCode::KeyR,
+            ResultsAction::NewRun => KeyCode::KeyN,
+            ResultsAction::Share => KeyCode::KeyC,
+            ResultsAction::Menu => KeyCode::KeyM,
+        }
+    }
+}
+
+/// Column in the top right corner for HUD elements that only show up now
+/// and then, like the combo counter
+#[derive(Component)]
+struct SideHud;
+
+/// Order of the gameplay systems within a fixed tick
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+enum GameplaySet {
+    /// Spawning new entities and ramping the difficulty
+    Spawn,
+    /// Moving the player and the camera
+    Movement,
+    /// Reacting to what the player touched after moving
+    Collision,
+}
+
+/// The gameplay sets in order. Gameplay only runs while playing, and never
+/// against a player that is missing mid-transition (e.g. while a run is
+/// being respawned).
+fn gameplay_sets() -> impl IntoSystemSetConfigs {
+    (
+        GameplaySet::Spawn,
+        GameplaySet::Movement,
+        GameplaySet::Collision,
+    )
+        .chain()
+        .run_if(in_state(GameState::Playing))
+        .run_if(any_with_component::<Player>)
+}
+
 // Game state
 #[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 enum GameState {
     #[default]
+    MainMenu,
     Playing,
+    Paused,
     GameOver,
+    /// Settings screen, opened from the main menu
+    Settings,
+}
+
+/// Sent once for every change of `GameState`, for systems that react to
+/// several transitions alike. `from` is `None` for the initial state.
+#[derive(Event, Debug, Clone, Copy)]
+struct GameStateChanged {
+    from: Option<GameState>,
+    to: GameState,
+}
+
+/// Turn bevy's transitions into `GameStateChanged`, leaving out the ones
+/// that re-enter the same state
+fn emit_state_changes(
+    mut transitions: EventReader<StateTransitionEvent<GameState>>,
+    mut changes: EventWriter<GameStateChanged>,
+) {
+    for transition in transitions.read() {
+        let Some(to) = transition.entered else {
+            continue;
+        };
+        if transition.exited != Some(to) {
+            debug!(from = ?transition.exited, ?to, "game state changed");
+            changes.send(GameStateChanged {
+                from: transition.exited,
+                to,
+            });
+        }
+    }
+}
+
+fn pause_game_clock(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn resume_game_clock(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn dash(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut stamina: ResMut<Stamina>,
+    mut players: Query<(Entity, &PlayerId, Option<&mut Dashing>), With<Player>>,
+    time: Res<Time>,
+) {
+    // Players share the stamina, which only regenerates while nobody dashes
+    let mut anyone_dashing = false;
+
+    for (player_entity, id, dashing) in &mut players {
+        if let Some(mut dashing) = dashing {
+            if dashing.tick(time.delta()).finished() {
+                commands.entity(player_entity).remove::<Dashing>();
+            }
+            anyone_dashing = true;
+            continue;
+        }
+
+        // Holding the key keeps dashing for as long as there is stamina to spend
+        if keyboard_input.pressed(id.controls().dash) && **stamina >= DASH_STAMINA_COST {
+            **stamina -= DASH_STAMINA_COST;
+            commands
+                .entity(player_entity)
+                .insert(Dashing(Timer::from_seconds(DASH_DURATION, TimerMode::Once)));
+            anyone_dashing = true;
+        }
+    }
+
+    if !anyone_dashing {
+        **stamina = (**stamina + STAMINA_REGEN_RATE * time.delta_secs()).min(MAX_STAMINA);
+    }
 }
 
+#[allow(clippy::type_complexity)]
 fn move_player(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut player_transform: Single<&mut Transform, With<Player>>,
+    gamepads: Query<&Gamepad>,
+    settings: Res<GameSettings>,
+    freeze: Res<ScrollFreeze>,
+    mut players: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut Throttle,
+            &PlayerId,
+            Has<Dashing>,
+        ),
+        With<Player>,
+    >,
     time: Res<Time>,
 ) {
-    let mut vertical = 0.0;
+    for (mut player_transform, mut velocity, mut throttle, id, is_dashing) in &mut players {
+        let mut vertical = 0.0;
 
-    if keyboard_input.pressed(KeyCode::ArrowUp) {
-        vertical += 1.0;
+        if keyboard_input.pressed(id.controls().up) {
+            vertical += 1.0;
+        }
+        if keyboard_input.pressed(id.controls().down) {
+            vertical -= 1.0;
+        }
+        if id.0 == 0 {
+            for gamepad in &gamepads {
+                let stick = gamepad.get(GamepadAxis::LeftStickY).unwrap_or_default();
+                vertical += apply_deadzone(stick, settings.deadzone);
+            }
+        }
+        let vertical = f32::clamp(vertical, -1.0, 1.0);
+        let vertical = if settings.invert_vertical {
+            -vertical
+        } else {
+            vertical
+        };
+
+        let target_throttle = if keyboard_input.pressed(settings.brake_keys[id.0]) {
+            settings.brake_min_speed.clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let ease = (BRAKE_EASING * time.delta_secs()).min(1.0);
+        **throttle += (target_throttle - **throttle) * ease;
+
+        let horizontal_speed = if freeze.is_active() {
+            0.0
+        } else if is_dashing {
+            SCROLL_SPEED * DASH_SPEED_MULTIPLIER * **throttle
+        } else {
+            SCROLL_SPEED * **throttle
+        };
+        let vertical_speed = 300.0;
+
+        velocity.x = horizontal_speed; // constant scroll right
+        velocity.y = vertical * vertical_speed; // up/down input
+
+        player_transform.translation += velocity.extend(0.0) * time.delta_secs();
     }
-    if keyboard_input.pressed(KeyCode::ArrowDown) {
-        vertical -= 1.0;
+}
+
+/// Map analog axis values inside `deadzone` to zero, rescaling the rest so
+/// the output still starts from zero at the edge of the deadzone
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    let deadzone = deadzone.clamp(0.0, MAX_DEADZONE);
+    if value.abs() <= deadzone {
+        return 0.0;
     }
+    value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+}
 
-    let horizontal_speed = 300.0;
-    let vertical_speed = 300.0;
+/// Steer the rug vertically towards the cursor while the left mouse button is
+/// held. Keyboard input still applies, this only eases the height on top.
+fn mouse_control(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut players: Query<(&mut Transform, &PlayerId), With<Player>>,
+    time: Res<Time>,
+) {
+    if !mouse_input.pressed(MouseButton::Left) {
+        return;
+    }
+    let Some((mut player_transform, _)) = players.iter_mut().find(|(_, id)| id.0 == 0) else {
+        return;
+    };
 
-    let movement = Vec3::new(
-        horizontal_speed * time.delta_secs(), // constant scroll right
-        vertical * vertical_speed * time.delta_secs(), // up/down input
-        0.0,
-    );
+    let (camera, camera_transform) = *camera;
+    let Some(target) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())
+    else {
+        return;
+    };
 
-    player_transform.translation += movement;
+    let ease = (MOUSE_FOLLOW_RATE * time.delta_secs()).min(1.0);
+    player_transform.translation.y += (target.y - player_transform.translation.y) * ease;
 }
 
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 fn follow_player(
-    player_transform: Query<&Transform, With<Player>>,
-    mut camera_transform: Query<&mut Transform, (With<Camera2d>, Without<Player>)>,
+    player_query: Query<(&Transform, &Velocity), With<Player>>,
+    mut camera_transform: Query<
+        (&mut Transform, &OrthographicProjection),
+        (With<MainCamera>, Without<Player>),
+    >,
+    obstacles: Query<&Transform, (With<Obstacle>, Without<MainCamera>, Without<Player>)>,
+    settings: Res<GameSettings>,
+    mut lead: Local<f32>,
+    mut catching_up: Local<bool>,
+    mut nudge: Local<f32>,
+    time: Res<Time>,
+) {
+    // Frame the trailing rug, so nobody falls behind the screen
+    let Some((player, velocity)) = player_query
+        .iter()
+        .min_by(|(a, _), (b, _)| a.translation.x.total_cmp(&b.translation.x))
+    else {
+        return;
+    };
+    let Ok((mut camera, projection)) = camera_transform.get_single_mut() else {
+        return;
+    };
+
+    // Look further ahead the faster we go, easing so speed changes don't jitter
+    let max_lead = settings.camera_lead_x.abs() * CAMERA_MAX_LEAD_RATIO;
+    let target_lead =
+        (velocity.x / SCROLL_SPEED * settings.camera_lead_x).clamp(-max_lead, max_lead);
+    let gap = (target_lead - *lead).abs();
+    if gap > CAMERA_CATCH_UP_START {
+        *catching_up = true;
+    } else if gap < CAMERA_CATCH_UP_STOP {
+        *catching_up = false;
+    }
+    let easing = if *catching_up {
+        CAMERA_CATCH_UP_EASING
+    } else {
+        CAMERA_LEAD_EASING
+    };
+    let ease = (easing * time.delta_secs()).min(1.0);
+    *lead += (target_lead - *lead) * ease;
+
+    camera.translation.x = player.translation.x + *lead;
+    if let Some(bounds) = settings.camera_bounds {
+        camera.translation.x = bounds.clamp(camera.translation.x, projection.area.width() / 2.0);
+    }
+
+    // Drift up or down towards obstacles coming up ahead, as a hint of where
+    // the danger is. Added as an offset so camera shake still applies.
+    let target_nudge = if settings.camera_danger_nudge {
+        let pull: f32 = obstacles
+            .iter()
+            .map(|obstacle| obstacle.translation - camera.translation)
+            .filter(|to_obstacle| (0.0..DANGER_NUDGE_RANGE).contains(&to_obstacle.x))
+            .map(|to_obstacle| {
+                let closeness = 1.0 - to_obstacle.x / DANGER_NUDGE_RANGE;
+                to_obstacle.y.signum() * closeness
+            })
+            .sum();
+        pull.clamp(-1.0, 1.0) * DANGER_NUDGE
+    } else {
+        0.0
+    };
+    let ease = (DANGER_NUDGE_EASING * time.delta_secs()).min(1.0);
+    let new_nudge = *nudge + (target_nudge - *nudge) * ease;
+    camera.translation.y += new_nudge - *nudge;
+    *nudge = new_nudge;
+}
+
+/// Gently pull gems that sit in a narrow cone ahead of each player's
+/// movement. Pulled gems drag their neighbours along, up to
+/// `MAX_GEM_CHAIN_DEPTH` links away.
+#[allow(clippy::type_complexity)]
+fn pull_gems_in_cone(
+    players: Query<(&Transform, &Velocity), With<Player>>,
+    mut gem_query: Query<(&mut Transform, &GemKind), (With<Gem>, Without<Player>)>,
+    time: Res<Time>,
 ) {
-    let player = player_transform.single();
-    let mut camera = camera_transform.single_mut();
-    camera.translation.x = player.translation.x + 200.0; // Look ahead a bit
+    let min_alignment = GEM_CONE_HALF_ANGLE.cos();
+
+    for (player_transform, velocity) in &players {
+        let player_pos = player_transform.translation.truncate();
+        let direction = velocity.normalize_or_zero();
+
+        // Pulling in a mimic would make it impossible to dodge
+        let mut gems: Vec<_> = gem_query
+            .iter_mut()
+            .filter(|(_, kind)| **kind != GemKind::Mimic)
+            .map(|(transform, _)| transform)
+            .collect();
+        let positions: Vec<Vec2> = gems
+            .iter()
+            .map(|transform| transform.translation.truncate())
+            .collect();
+
+        // How many links each gem is from one in the cone, if it is pulled
+        let mut depths: Vec<Option<usize>> = positions
+            .iter()
+            .map(|position| {
+                let to_gem = *position - player_pos;
+                let in_cone = to_gem.length() <= GEM_CONE_RANGE
+                    && direction.dot(to_gem.normalize_or_zero()) >= min_alignment;
+                in_cone.then_some(0)
+            })
+            .collect();
+        for depth in 0..MAX_GEM_CHAIN_DEPTH {
+            let pulling: Vec<Vec2> = positions
+                .iter()
+                .zip(&depths)
+                .filter(|(_, gem_depth)| **gem_depth == Some(depth))
+                .map(|(position, _)| *position)
+                .collect();
+            if pulling.is_empty() {
+                break;
+            }
+
+            for (position, gem_depth) in positions.iter().zip(&mut depths) {
+                if gem_depth.is_none()
+                    && pulling
+                        .iter()
+                        .any(|puller| puller.distance(*position) < GEM_CHAIN_RADIUS)
+                {
+                    *gem_depth = Some(depth + 1);
+                }
+            }
+        }
+
+        for (transform, gem_depth) in gems.iter_mut().zip(&depths) {
+            if gem_depth.is_none() {
+                continue;
+            }
+
+            let to_gem = transform.translation.truncate() - player_pos;
+            let pull = -to_gem.normalize_or_zero() * GEM_CONE_PULL * time.delta_secs();
+            transform.translation += pull.extend(0.0);
+        }
+    }
 }
 
+/// Sort gems picked up in the same tick by their distance to the nearest
+/// rug, closest first, so per-pickup effects apply in a set order. Gems at
+/// the same distance go by entity, rather than the order the query saw them.
+fn sort_pickups<T>(picked: &mut [(f32, Entity, T)]) {
+    picked.sort_by(|(a_distance, a, _), (b_distance, b, _)| {
+        a_distance.total_cmp(b_distance).then(a.cmp(b))
+    });
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn collect_gems(
     mut commands: Commands,
     mut score: ResMut<Score>,
-    mut player_query: Query<(&Transform, &mut Health), With<Player>>,
-    gem_query: Query<(Entity, &Transform), With<Gem>>,
-    sound: Res<CollisionSound>,
+    mut stats: ResMut<Stats>,
+    mut milestones: EventWriter<GemMilestone>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut split_events: EventWriter<SplitGemCollected>,
+    mut rewind_events: EventWriter<RewindCollected>,
+    mut freeze_events: EventWriter<FreezeCollected>,
+    mut double_points_events: EventWriter<DoublePointsCollected>,
+    mut extra_life_events: EventWriter<ExtraLifeCollected>,
+    player_query: Query<&Transform, With<Player>>,
+    gem_query: Query<
+        (
+            Entity,
+            &Transform,
+            &GemKind,
+            &GemValue,
+            &Charge,
+            Has<SplitGem>,
+            Has<RewindGem>,
+            Has<FreezeGem>,
+            Has<DoublePoints>,
+            Has<ExtraLife>,
+        ),
+        With<Gem>,
+    >,
+    camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    (sound, volumes): (Res<CollisionSound>, Volumes),
+    settings: Res<GameSettings>,
+    multiplier: Res<ScoreMultiplier>,
 ) {
-    let (player_transform, mut health) = player_query.single_mut();
-    let player_pos = player_transform.translation.truncate();
+    let _span = debug_span!("collect_gems").entered();
+    let (camera, camera_transform) = *camera;
+    let pickup_radius = GEM_PICKUP_RADIUS * settings.assist_mode.pickup_radius_scale();
 
-    for (gem_entity, transform) in &gem_query {
-        if player_pos.distance(transform.translation.truncate()) < 30.0 {
-            // Remove gem entity
-            commands.entity(gem_entity).despawn();
+    let mut picked: Vec<_> = gem_query
+        .iter()
+        // Touching a mimic is handled as a hit instead
+        .filter(|(_, _, kind, ..)| **kind != GemKind::Mimic)
+        .filter_map(|gem| {
+            // Any player can pick a gem up, which counts towards the shared
+            // score
+            let gem_pos = gem.1.translation.truncate();
+            let distance = player_query
+                .iter()
+                .map(|player| player.translation.truncate().distance(gem_pos))
+                .min_by(f32::total_cmp)?;
+            (distance < pickup_radius).then_some((distance, gem.0, gem))
+        })
+        .collect();
+    sort_pickups(&mut picked);
 
-            // Update score
-            **score += 1;
+    for (
+        _,
+        _,
+        (
+            gem_entity,
+            transform,
+            kind,
+            value,
+            charge,
+            is_split,
+            is_rewind,
+            is_freeze,
+            is_double_points,
+            is_extra_life,
+        ),
+    ) in picked
+    {
+        let gem_pos = transform.translation.truncate();
+        // Remove gem entity
+        commands.entity(gem_entity).despawn_recursive();
 
-            // Simulate health loss for demo
-            health.current = (health.current - 1).max(0);
+        // Update score, worth more the further into the run, the longer
+        // the gem was left to charge and while double points are active
+        let value =
+            **value as f32 * stats.distance_bonus() * charge.multiplier() * multiplier.current();
+        let points = value.round() as usize;
+        **score += points;
+        debug!(
+            kind = kind.name(),
+            points,
+            x = gem_pos.x,
+            y = gem_pos.y,
+            "gem collected"
+        );
 
-            // Play sound effect
-            commands.spawn((AudioPlayer(sound.clone()), PlaybackSettings::DESPAWN));
+        stats.gems_collected += 1;
+        if stats.gems_collected.is_multiple_of(GEM_MILESTONE_INTERVAL) {
+            milestones.send(GemMilestone {
+                gems_collected: stats.gems_collected,
+            });
+        }
+
+        collision_events.send(CollisionEvent {
+            position: transform.translation.truncate(),
+            kind: *kind,
+            points,
+        });
+        if is_split {
+            split_events.send(SplitGemCollected {
+                position: transform.translation.truncate(),
+            });
+        }
+        if is_rewind {
+            rewind_events.send(RewindCollected);
+        }
+        if is_freeze {
+            freeze_events.send(FreezeCollected);
+        }
+        if is_double_points {
+            double_points_events.send(DoublePointsCollected);
+        }
+        if is_extra_life {
+            extra_life_events.send(ExtraLifeCollected);
+        }
+
+        // Play sound effect, from the side of the screen the gem was on
+        let playback = PlaybackSettings::DESPAWN.with_volume(volumes.sfx());
+        if settings.pan_pickup_sounds {
+            let pan = screen_pan(camera, camera_transform, gem_pos);
+            let offset = Vec3::X * pan * PICKUP_EAR_GAP / 2.0;
+            commands.spawn((
+                AudioPlayer(sound.clone()),
+                playback.with_spatial(true),
+                Transform::from_translation(camera_transform.translation() + offset),
+            ));
+        } else {
+            commands.spawn((AudioPlayer(sound.clone()), playback));
         }
     }
 }
 
-// Add the game's entities to our world
+/// Where `position` is across the screen, from -1 at the left edge through 0
+/// in the middle to 1 at the right edge
+fn screen_pan(camera: &Camera, camera_transform: &GlobalTransform, position: Vec2) -> f32 {
+    let (Ok(viewport), Some(size)) = (
+        camera.world_to_viewport(camera_transform, position.extend(0.0)),
+        camera.logical_viewport_size(),
+    ) else {
+        return 0.0;
+    };
+    (viewport.x / size.x * 2.0 - 1.0).clamp(-1.0, 1.0)
+}
+
+fn update_difficulty(
+    mut difficulty: ResMut<Difficulty>,
+    mut stats: ResMut<Stats>,
+    curve: Res<DifficultyCurve>,
+    time: Res<Time>,
+) {
+    stats.elapsed += time.delta_secs();
+    **difficulty = curve.evaluate(stats.elapsed);
+}
+
+/// Check whether two axis-aligned boxes, given by their centers and full
+/// sizes, overlap
+fn aabb_overlap(a_center: Vec2, a_size: Vec2, b_center: Vec2, b_size: Vec2) -> bool {
+    let distance = (a_center - b_center).abs();
+    let reach = (a_size + b_size) / 2.0;
+    distance.x < reach.x && distance.y < reach.y
+}
+
+// Add the entities that live for the whole session to our world
 fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     // Spawn Camera
-    commands.spawn(Camera2d);
+    commands.spawn((
+        Camera2d,
+        MainCamera,
+        IsDefaultUiCamera,
+        SpatialListener::new(PICKUP_EAR_GAP),
+    ));
+
+    // Add Sound (gets played by the gem collection function)
+    let ball_collision_sound = asset_server.load("sounds/gem_collection.ogg");
+    commands.insert_resource(CollisionSound(ball_collision_sound));
 
-    // Spawn Player
+    // Game Over UI, centered over the whole window
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|p| {
+            p.spawn((
+                Text::new(""), // Empty string -- invisible but we will append to it when the game is over
+                TextFont::default(),
+                HudText(1.0),
+                TextColor(TEXT_COLOR),
+                TextLayout::new_with_justify(JustifyText::Center),
+                GameOverUi,
+            ))
+            .with_children(|p| {
+                p.spawn((
+                    TextSpan::default(),
+                    TextFont::default(),
+                    HudText(4.0),
+                    TextColor(RED_TEXT),
+                ));
+                p.spawn((
+                    TextSpan::default(),
+                    TextFont::default(),
+                    HudText(1.0),
+                    TextColor(TEXT_COLOR),
+                ));
+            });
+        });
+
+    // Top right HUD column, filled in by the plugins that own its elements
     commands.spawn((
-        Sprite {
-            image: asset_server.load("sprites/rug.png"),
-            custom_size: Some(Vec2::new(PLAYER_SIZE, PLAYER_SIZE)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: SCOREBOARD_TEXT_PADDING,
+            right: SCOREBOARD_TEXT_PADDING,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::End,
+            row_gap: SCOREBOARD_TEXT_PADDING,
             ..default()
         },
-        Player,
-        Health {
-            current: MAX_HEALTH,
-            max: MAX_HEALTH,
-        },
+        SideHud,
     ));
 
-    // Spawn Gems
-    for i in 0..100 {
-        let x = i as f32 * 300.0 + 600.0; // Spread out along the scroll
-        let y = rand::random::<f32>() * 400.0 - 200.0;
+    // Top left HUD column; stacking the elements lets them reflow with the
+    // font size instead of overlapping
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: SCOREBOARD_TEXT_PADDING,
+            left: SCOREBOARD_TEXT_PADDING,
+            flex_direction: FlexDirection::Column,
+            row_gap: SCOREBOARD_TEXT_PADDING,
+            ..default()
+        })
+        .with_children(|hud| {
+            // Scoreboard UI
+            hud.spawn((
+                Text::new("Score: "),
+                TextFont::default(),
+                HudText(1.0),
+                TextColor(TEXT_COLOR),
+                ScoreboardUi,
+            ))
+            .with_children(|p| {
+                p.spawn((
+                    TextSpan::default(),
+                    TextFont::default(),
+                    HudText(1.0),
+                    TextColor(SCORE_COLOR),
+                ));
+                // distance bonus
+                p.spawn((
+                    TextSpan::default(),
+                    TextFont::default(),
+                    HudText(0.75),
+                    TextColor(TEXT_COLOR),
+                ));
+            });
+
+            // Health UI
+            hud.spawn((
+                Text::new("Health: "),
+                TextFont::default(),
+                HudText(1.0),
+                TextColor(TEXT_COLOR),
+                HealthUi,
+            ))
+            .with_child((
+                TextSpan::default(),
+                TextFont::default(),
+                HudText(1.0),
+                TextColor(GREEN_TEXT),
+            ));
+            hud.spawn((
+                Node {
+                    column_gap: Val::Px(HEART_GAP),
+                    display: Display::None,
+                    ..default()
+                },
+                HeartsUi,
+            ));
+
+            // Health bar UI
+            hud.spawn((
+                Node {
+                    width: Val::Px(HEALTH_BAR_SIZE.x),
+                    height: Val::Px(HEALTH_BAR_SIZE.y),
+                    ..default()
+                },
+                BackgroundColor(STAMINA_BAR_BACKGROUND),
+            ))
+            .with_child((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(HEALTH_BAR_COLOR),
+                HealthBarUi { target: 1.0 },
+            ));
+
+            // Stamina UI
+            hud.spawn((
+                Node {
+                    width: Val::Px(STAMINA_BAR_SIZE.x),
+                    height: Val::Px(STAMINA_BAR_SIZE.y),
+                    ..default()
+                },
+                BackgroundColor(STAMINA_BAR_BACKGROUND),
+            ))
+            .with_child((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(STAMINA_COLOR),
+                StaminaUi,
+            ));
+        });
+}
+
+// Add the entities for a single run to our world
+fn spawn_level(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<GameSettings>,
+) {
+    let (start_x, start_y) = settings.player_start;
+    let player_count = if settings.coop { 2 } else { 1 };
+
+    // Start in a clear stretch, and can't be hurt until through it
+    obstacles::spawn_safe_lane(&mut commands, start_x, RESPAWN_SAFE_DISTANCE);
+    let grace = RESPAWN_SAFE_DISTANCE / SCROLL_SPEED;
+
+    // Spawn Players, spread out vertically around the start
+    for i in 0..player_count {
+        let offset = (i as f32 - (player_count - 1) as f32 / 2.0) * COOP_START_SPACING;
+        let color = if i == 0 {
+            Color::WHITE
+        } else {
+            SECOND_PLAYER_COLOR
+        };
 
         commands.spawn((
             Sprite {
-                image: asset_server.load("sprites/gem.png"),
-                custom_size: Some(Vec2::new(GEM_SIZE, GEM_SIZE)),
+                image: asset_server.load("sprites/rug.png"),
+                custom_size: Some(Vec2::new(PLAYER_SIZE, PLAYER_SIZE)),
+                color,
                 ..default()
             },
-            Transform {
-                translation: Vec3::new(x, y, 0.0),
-                // scale: Vec3::splat(20.0),
-                ..default()
+            Transform::from_xyz(start_x, start_y - offset, PLAYER_Z),
+            Player,
+            PlayerId(i),
+            Health {
+                current: MAX_HEALTH,
+                max: MAX_HEALTH,
+                partial: 0.0,
             },
-            Gem,
-            Collider,
+            Velocity::default(),
+            Throttle::default(),
+            Invulnerable::new(grace),
+            RunEntity,
         ));
     }
+    commands.send_event(HealthChanged {
+        current: MAX_HEALTH,
+        max: MAX_HEALTH,
+    });
+}
 
-    // Add Sound (gets played by the gem collection function)
-    let ball_collision_sound = asset_server.load("sounds/gem_collection.ogg");
-    commands.insert_resource(CollisionSound(ball_collision_sound));
+// Remove everything left over from the previous run and reset its resources
+#[allow(clippy::too_many_arguments)]
+fn cleanup_run(
+    mut commands: Commands,
+    run_entities: Query<Entity, With<RunEntity>>,
+    mut camera_transform: Query<&mut Transform, With<MainCamera>>,
+    mut score: ResMut<Score>,
+    mut difficulty: ResMut<Difficulty>,
+    mut stamina: ResMut<Stamina>,
+    mut graze_score: ResMut<GrazeScore>,
+    mut in_flight: ResMut<ScoreInFlight>,
+    mut rng: ResMut<GameRng>,
+    mode: Res<GameMode>,
+) {
+    for entity in &run_entities {
+        commands.entity(entity).despawn_recursive();
+    }
 
-    // Game Over UI
-    commands
-        .spawn((
-            // FIXME: This doesn't center! How do I make it work?
-            Node {
-                position_type: PositionType::Absolute,
-                // display: Display::Flex,
-                // width: Val::Percent(100.0),
-                // height: Val::Percent(100.0),
-                // justify_content: JustifyContent::Center,
-                // align_items: AlignItems::Center,
-                top: SCOREBOARD_TEXT_PADDING * 20.0,
-                left: SCOREBOARD_TEXT_PADDING * 20.0,
-                ..default()
-            },
-            Text::new(""), // Empty string -- invisible but we will append to it when the game is over
-            TextFont {
-                font_size: SCOREBOARD_FONT_SIZE,
-                ..default()
-            },
-            TextColor(TEXT_COLOR),
-            GameOverUi,
-        ))
-        .with_child((
-            TextSpan::default(),
-            TextFont {
-                font_size: SCOREBOARD_FONT_SIZE * 4.0,
-                ..default()
-            },
-            TextColor(RED_TEXT),
-        ));
+    if let Ok(mut camera) = camera_transform.get_single_mut() {
+        camera.translation.x = 0.0;
+    }
 
-    // Scoreboard UI
-    commands
-        .spawn((
-            Text::new("Score: "),
-            TextFont {
-                font_size: SCOREBOARD_FONT_SIZE,
-                ..default()
-            },
-            TextColor(TEXT_COLOR),
-            ScoreboardUi,
-            Node {
-                position_type: PositionType::Absolute,
-                top: SCOREBOARD_TEXT_PADDING,
-                left: SCOREBOARD_TEXT_PADDING,
-                ..default()
-            },
-        ))
-        .with_child((
-            TextSpan::default(),
-            TextFont {
-                font_size: SCOREBOARD_FONT_SIZE,
-                ..default()
-            },
-            TextColor(SCORE_COLOR),
-        ));
+    **score = 0;
+    **difficulty = 1.0;
+    **stamina = MAX_STAMINA;
+    **graze_score = 0;
+    **in_flight = 0;
+    *rng = mode.run_rng();
+    // Replaced through commands rather than taken as parameters, which would
+    // go past the most a system can have. They are in place before the
+    // next run's systems read them.
+    commands.insert_resource(Stats::default());
+    commands.insert_resource(RunFlags::default());
+    commands.insert_resource(Combo::default());
+    commands.insert_resource(GemStream::default());
+    commands.insert_resource(CollectionRatio::default());
+    commands.insert_resource(NoHitStreak::default());
+    commands.insert_resource(StateHistory::default());
+    commands.insert_resource(TimeOfDay::default());
+    commands.insert_resource(PickupTrail::default());
+    commands.insert_resource(Fuel::default());
+    commands.insert_resource(ScrollFreeze::default());
+    commands.insert_resource(ScoreMultiplier::default());
+    commands.insert_resource(Lives::default());
+    commands.insert_resource(BeatClock::default());
+    commands.queue(obstacles::reset_spawners);
+}
 
-    // Health UI
-    commands
+/// End the run once every rug is out of health, or the settings' other death
+/// condition is met. Until then, rugs that ran out of health are removed.
+///
+/// Running out of health while holding a big enough combo revives the rugs
+/// instead, once per run.
+#[allow(clippy::too_many_arguments)]
+fn check_player_death(
+    mut commands: Commands,
+    mut players: Query<(Entity, &mut Health), With<Player>>,
+    settings: Res<GameSettings>,
+    fuel: Res<Fuel>,
+    stats: Res<Stats>,
+    combo: Res<Combo>,
+    mut flags: ResMut<RunFlags>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let condition_met = match settings.death_condition {
+        DeathCondition::Health => false,
+        DeathCondition::Fuel => fuel.is_empty(),
+        DeathCondition::Timer(limit) => stats.elapsed >= limit,
+    };
+    let all_dead = players.iter().all(|(_, health)| health.current <= 0);
+    let second_wind = !flags.second_wind_used
+        && settings
+            .second_wind_combo
+            .is_some_and(|threshold| combo.count >= threshold);
+    if all_dead && !condition_met && second_wind {
+        debug!(combo = combo.count, "second wind");
+        flags.second_wind_used = true;
+        for (entity, mut health) in &mut players {
+            health.current = SECOND_WIND_HEALTH.min(health.max);
+            health.partial = 0.0;
+            commands.entity(entity).insert(Invulnerable::after_hit());
+            commands.send_event(HealthChanged {
+                current: health.current,
+                max: health.max,
+            });
+        }
+        flash::spawn_screen_flash(
+            &mut commands,
+            SECOND_WIND_FLASH_COLOR,
+            SECOND_WIND_FLASH_DURATION,
+        );
+        return;
+    }
+    if condition_met || all_dead {
+        // println!("Game Over!");
+        next_state.set(GameState::GameOver);
+        return;
+    }
+
+    for (entity, health) in &players {
+        if health.current <= 0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Retry the same layout, start a fresh one, copy the results or go back to
+/// the menu. The new run's generator is built from `GameMode` when
+/// `cleanup_run` leaves this state, before `spawn_level` runs.
+#[allow(clippy::too_many_arguments)]
+fn handle_game_over_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut activated: EventReader<MenuActivated>,
+    mut actions: Query<(&ResultsAction, &mut Text)>,
+    delay: Res<GameOverDelay>,
+    rng: Res<GameRng>,
+    score: Res<Score>,
+    stats: Res<Stats>,
+    mut mode: ResMut<GameMode>,
+    mut clipboard: NonSendMut<share::SystemClipboard>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !delay.finished() {
+        activated.clear();
+        return;
+    }
+
+    let hotkey = ResultsAction::ALL
+        .into_iter()
+        .find(|action| keyboard_input.just_pressed(action.hotkey()));
+    let Some(action) = activated
+        .read()
+        .find_map(|event| actions.get(event.0).ok().map(|(action, _)| *action))
+        .or(hotkey)
+    else {
+        return;
+    };
+
+    match action {
+        ResultsAction::Share => {
+            let text = share::share_text(**score, &stats, rng.seed);
+            let label = if clipboard.copy(&text) {
+                "Copied!"
+            } else {
+                "Couldn't copy the results"
+            };
+            for (action, mut text) in &mut actions {
+                if *action == ResultsAction::Share {
+                    text.0 = label.to_string();
+                }
+            }
+        }
+        ResultsAction::RetrySeed => {
+            *mode = match *mode {
+                // the daily layout already repeats, and stays a daily attempt
+                GameMode::Daily => GameMode::Daily,
+                GameMode::TimeAttack(_) => GameMode::TimeAttack(Some(rng.seed)),
+                GameMode::Endless | GameMode::Seeded(_) => GameMode::Seeded(rng.seed),
+            };
+            next_state.set(GameState::Playing);
+        }
+        ResultsAction::NewRun => {
+            *mode = match *mode {
+                GameMode::TimeAttack(_) => GameMode::TimeAttack(None),
+                _ => GameMode::Endless,
+            };
+            next_state.set(GameState::Playing);
+        }
+        ResultsAction::Menu => next_state.set(GameState::MainMenu),
+    }
+}
+
+fn start_game_over_delay(mut commands: Commands) {
+    commands.insert_resource(GameOverDelay(Timer::from_seconds(
+        GAME_OVER_INPUT_DELAY,
+        TimerMode::Once,
+    )));
+}
+
+/// Show the options once the delay is up
+fn reveal_results_menu(mut commands: Commands, mut delay: ResMut<GameOverDelay>, time: Res<Time>) {
+    if !delay.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let menu = commands
         .spawn((
-            Text::new("Health: "),
-            TextFont {
-                font_size: SCOREBOARD_FONT_SIZE,
-                ..default()
-            },
-            TextColor(TEXT_COLOR),
-            HealthUi,
             Node {
                 position_type: PositionType::Absolute,
-                top: SCOREBOARD_TEXT_PADDING * 10.0,
-                left: SCOREBOARD_TEXT_PADDING,
+                top: Val::Percent(70.0),
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
                 ..default()
             },
+            StateScoped(GameState::GameOver),
         ))
-        .with_child((
-            TextSpan::default(),
-            TextFont {
-                font_size: SCOREBOARD_FONT_SIZE,
-                ..default()
-            },
-            TextColor(GREEN_TEXT),
-        ));
-}
+        .id();
 
-fn check_player_death(
-    player: Query<&Health, With<Player>>,
-    mut next_state: ResMut<NextState<GameState>>,
-) {
-    let health = player.single();
-    if health.current <= 0 {
-        // println!("Game Over!");
-        next_state.set(GameState::GameOver);
+    for (order, action) in ResultsAction::ALL.into_iter().enumerate() {
+        menu::spawn_menu_item(&mut commands, menu, order, action.label(), action);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn show_game_over(
     state: Res<State<GameState>>,
-    game_over_root: Single<Entity, (With<GameOverUi>, With<Text>)>,
+    rng: Res<GameRng>,
+    settings: Res<GameSettings>,
+    mode: Res<GameMode>,
+    stats: Res<Stats>,
+    fuel: Res<Fuel>,
+    game_over_root: Query<Entity, (With<GameOverUi>, With<Text>)>,
     mut writer: TextUiWriter,
 ) {
+    let Ok(game_over_root) = game_over_root.get_single() else {
+        return;
+    };
+    let (out_of_fuel, out_of_time) = match settings.death_condition {
+        DeathCondition::Health => (false, false),
+        DeathCondition::Fuel => (fuel.is_empty(), false),
+        DeathCondition::Timer(limit) => (false, stats.elapsed >= limit),
+    };
     let message = match state.get() {
+        GameState::GameOver if time_attack::finished(&mode, &stats) => "FINISHED",
+        GameState::GameOver if out_of_fuel => "OUT OF FUEL",
+        GameState::GameOver if out_of_time => "TIME'S UP",
         GameState::GameOver => "YOU DIED",
         _ => "", // Clear the message if not dead
     };
+    let assisted = match settings.assist_mode {
+        AssistMode::On => "\nAssisted run, not ranked",
+        AssistMode::Off => "",
+    };
+    let prompt = match state.get() {
+        GameState::GameOver => format!("{assisted}\nSeed: {}", rng.seed),
+        _ => String::new(),
+    };
 
-    *writer.text(*game_over_root, 1) = message.to_string();
+    *writer.text(game_over_root, 1) = message.to_string();
+    *writer.text(game_over_root, 2) = prompt;
 }
 
+/// Remember the finished run's seed so it can be replayed from the menu
+fn record_last_seed(rng: Res<GameRng>, mut save: ResMut<SaveData>) {
+    save.last_seed = Some(rng.seed);
+    save.save();
+}
+
+/// Fill in the health display the settings ask for. The hearts are spawned
+/// again whenever the mode or anyone's health changes.
 fn update_health_ui(
-    player: Query<&Health, With<Player>>,
-    health_root: Single<Entity, (With<HealthUi>, With<Text>)>,
+    mut commands: Commands,
+    settings: Res<GameSettings>,
+    players: Query<(&PlayerId, Ref<Health>), With<Player>>,
+    mut removed_players: RemovedComponents<Player>,
+    health_root: Query<Entity, (With<HealthUi>, With<Text>)>,
+    hearts_root: Query<Entity, With<HeartsUi>>,
     mut writer: TextUiWriter,
 ) {
-    let health = player.single();
-    *writer.text(*health_root, 1) = format!("{}/{}", health.current, health.max);
+    let (Ok(health_root), Ok(hearts_root)) = (health_root.get_single(), hearts_root.get_single())
+    else {
+        return;
+    };
+    let mut healths: Vec<_> = players.iter().collect();
+    healths.sort_by_key(|(id, _)| **id);
+    let players_left = removed_players.read().count() > 0;
+
+    match settings.health_display {
+        HealthDisplayMode::Numeric => {
+            *writer.text(health_root, 1) = healths
+                .iter()
+                .map(|(_, health)| format!("{}/{}", health.current, health.max))
+                .collect::<Vec<_>>()
+                .join("  ");
+        }
+        HealthDisplayMode::Hearts => {
+            let changed = healths.iter().any(|(_, health)| health.is_changed());
+            if !settings.is_changed() && !changed && !players_left {
+                return;
+            }
+
+            commands
+                .entity(hearts_root)
+                .despawn_descendants()
+                .with_children(|row| {
+                    for (i, (_, health)) in healths.iter().enumerate() {
+                        // a wider gap between the rugs' hearts
+                        if i > 0 {
+                            row.spawn(Node {
+                                width: Val::Px(HEART_SIZE),
+                                ..default()
+                            });
+                        }
+                        for heart in 0..health.max {
+                            let color = if heart < health.current {
+                                HEALTH_BAR_COLOR
+                            } else {
+                                LOST_HEART_COLOR
+                            };
+                            row.spawn((
+                                Node {
+                                    width: Val::Px(HEART_SIZE),
+                                    height: Val::Px(HEART_SIZE),
+                                    ..default()
+                                },
+                                BorderRadius::MAX,
+                                BackgroundColor(color),
+                            ));
+                        }
+                    }
+                });
+        }
+        HealthDisplayMode::Bar => (),
+    }
+}
+
+/// Point the health bar at the players' combined health
+fn read_health_changes(
+    mut health_changes: EventReader<HealthChanged>,
+    players: Query<&Health, With<Player>>,
+    mut health_bar: Query<&mut HealthBarUi>,
+) {
+    if health_changes.is_empty() {
+        return;
+    }
+    health_changes.clear();
+    let Ok(mut health_bar) = health_bar.get_single_mut() else {
+        return;
+    };
+
+    let (current, max) = players.iter().fold((0, 0), |(current, max), health| {
+        (current + health.current, max + health.max)
+    });
+    if max > 0 {
+        health_bar.target = current as f32 / max as f32;
+    }
+}
+
+/// Ease the health bar towards the player's health instead of snapping
+fn tween_health_bar(mut health_bar: Query<(&mut Node, &HealthBarUi)>, time: Res<Time>) {
+    let Ok((mut node, health_bar)) = health_bar.get_single_mut() else {
+        return;
+    };
+    let Val::Percent(shown) = node.width else {
+        return;
+    };
+
+    let ease = (HEALTH_BAR_EASING * time.delta_secs()).min(1.0);
+    node.width = Val::Percent(shown + (health_bar.target * 100.0 - shown) * ease);
+}
+
+/// Cycle through the health display modes, showing the parts of the HUD
+/// the mode uses
+fn cycle_health_display(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<GameSettings>,
+    mut health_text: Query<&mut Node, (With<HealthUi>, Without<HeartsUi>)>,
+    mut hearts: Query<&mut Node, With<HeartsUi>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyH) {
+        settings.health_display = settings.health_display.cycled(1);
+        settings.save();
+    }
+
+    let (Ok(mut health_text), Ok(mut hearts)) =
+        (health_text.get_single_mut(), hearts.get_single_mut())
+    else {
+        return;
+    };
+    if settings.is_changed() {
+        // take the unused parts out of the layout entirely so the bar moves up
+        let display = |shown| if shown { Display::Flex } else { Display::None };
+        health_text.display = display(settings.health_display == HealthDisplayMode::Numeric);
+        hearts.display = display(settings.health_display == HealthDisplayMode::Hearts);
+    }
 }
 
+/// Show the score, minus the points still flying towards the scoreboard
+#[allow(clippy::too_many_arguments)]
 fn update_scoreboard(
+    mut commands: Commands,
     score: Res<Score>,
-    score_root: Single<Entity, (With<ScoreboardUi>, With<Text>)>,
+    stats: Res<Stats>,
+    settings: Res<GameSettings>,
+    in_flight: Res<ScoreInFlight>,
+    score_root: Query<Entity, (With<ScoreboardUi>, With<Text>)>,
     mut writer: TextUiWriter,
+    mut shown: Local<usize>,
+) {
+    let Ok(score_root) = score_root.get_single() else {
+        return;
+    };
+    let score = score.saturating_sub(**in_flight);
+    // pulse when the shown score goes up, not when a new run resets it
+    if score > *shown {
+        commands.entity(score_root).insert(Pulse {
+            timer: Timer::from_seconds(SCORE_PULSE_DURATION, TimerMode::Once),
+        });
+    }
+    *shown = score;
+
+    *writer.text(score_root, 1) = format_score(score, settings.score_format);
+    let bonus = stats.distance_bonus();
+    *writer.text(score_root, 2) = if bonus > 1.0 {
+        format!("  x{bonus:.2}")
+    } else {
+        String::new()
+    };
+}
+
+/// Write `score` out in `format`
+fn format_score(score: usize, format: ScoreFormat) -> String {
+    let digits = score.to_string();
+    if format == ScoreFormat::Plain {
+        return digits;
+    }
+
+    if format == ScoreFormat::Abbreviated && score >= SCORE_ABBREVIATION_THRESHOLD {
+        const UNITS: [(u128, &str); 4] = [
+            (1_000, "K"),
+            (1_000_000, "M"),
+            (1_000_000_000, "B"),
+            (1_000_000_000_000, "T"),
+        ];
+        // round to a tenth of the unit first, so 999,999 becomes 1.0M rather
+        // than 1000.0K
+        for (i, (unit, suffix)) in UNITS.into_iter().enumerate() {
+            let tenths = (score as u128 * 10 + unit / 2) / unit;
+            if tenths < 10_000 || i == UNITS.len() - 1 {
+                let whole = format_score((tenths / 10) as usize, ScoreFormat::Separated);
+                return format!("{whole}.{}{suffix}", tenths % 10);
+            }
+        }
+    }
+
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+fn track_distance(
+    players: Query<&Transform, With<Player>>,
+    settings: Res<GameSettings>,
+    mut stats: ResMut<Stats>,
+) {
+    let (start_x, _) = settings.player_start;
+    for transform in &players {
+        stats.distance = stats.distance.max(transform.translation.x - start_x);
+    }
+}
+
+fn pulse_ui(
+    mut commands: Commands,
+    mut pulses: Query<(Entity, &mut Pulse, &mut Transform)>,
+    time: Res<Time>,
+) {
+    for (entity, mut pulse, mut transform) in &mut pulses {
+        if pulse.timer.tick(time.delta()).finished() {
+            transform.scale = Vec3::ONE;
+            commands.entity(entity).remove::<Pulse>();
+            continue;
+        }
+
+        let swell = (pulse.timer.fraction() * std::f32::consts::PI).sin();
+        transform.scale = Vec3::splat(1.0 + SCORE_PULSE_SCALE * swell);
+    }
+}
+
+fn update_stamina_ui(stamina: Res<Stamina>, mut stamina_bar: Query<&mut Node, With<StaminaUi>>) {
+    let Ok(mut stamina_bar) = stamina_bar.get_single_mut() else {
+        return;
+    };
+    stamina_bar.width = Val::Percent(**stamina / MAX_STAMINA * 100.0);
+}
+
+fn apply_window_settings(
+    settings: Res<GameSettings>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
 ) {
-    *writer.text(*score_root, 1) = score.to_string();
+    window.present_mode = settings.present_mode.window_present_mode();
+}
+
+/// Sleep out the rest of the frame when it finished faster than the frame
+/// cap allows
+fn limit_frame_rate(settings: Res<GameSettings>, mut frame_start: Local<Option<Instant>>) {
+    let Some(cap) = settings.frame_cap.filter(|cap| *cap > 0.0) else {
+        return;
+    };
+
+    if let Some(start) = *frame_start {
+        let frame_time = Duration::from_secs_f32(1.0 / cap);
+        if let Some(left) = frame_time.checked_sub(start.elapsed()) {
+            std::thread::sleep(left);
+        }
+    }
+    *frame_start = Some(Instant::now());
+}
+
+fn apply_ui_scale(settings: Res<GameSettings>, mut ui_scale: ResMut<UiScale>) {
+    if settings.is_changed() {
+        ui_scale.0 = settings.ui_scale;
+    }
+}
+
+/// Size all HUD text from the settings, so it scales together
+fn apply_hud_font_size(
+    settings: Res<GameSettings>,
+    mut texts: Query<(Ref<HudText>, &mut TextFont)>,
+) {
+    for (hud_text, mut font) in &mut texts {
+        if settings.is_changed() || hud_text.is_added() {
+            font.font_size = settings.hud_font_size * hud_text.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, state::app::StatesPlugin, text::TextPlugin};
+
+    use super::*;
+    use settings::{CameraBounds, YDistribution};
+    use spawn_table::SpawnTable;
+
+    /// Heights and kinds of a stretch of gems, drawn the way `stream_gems`
+    /// draws them
+    fn gem_layout(rng: &mut GameRng) -> Vec<(f32, GemKind)> {
+        let table = SpawnTable::default();
+        (0..50)
+            .map(|_| {
+                let height = spawning::gem_height(&mut rng.rng, YDistribution::Uniform);
+                (height, table.random_gem(&mut rng.rng, 1.0))
+            })
+            .collect()
+    }
+
+    /// Fill in the resources `cleanup_run` resets, as they are during a run
+    /// of `mode`
+    fn insert_run_resources(world: &mut World, mode: GameMode) {
+        world.insert_resource(mode);
+        world.insert_resource(SpawnTable::default());
+        world.insert_resource(GameRng::from_seed(1));
+        world.insert_resource(Score(0));
+        world.insert_resource(Difficulty(1.0));
+        world.insert_resource(Stamina(MAX_STAMINA));
+        world.init_resource::<GrazeScore>();
+        world.init_resource::<ScoreInFlight>();
+    }
+
+    #[test]
+    fn same_seed_lays_out_the_same_gems() {
+        let layout = gem_layout(&mut GameRng::from_seed(42));
+        assert_eq!(layout, gem_layout(&mut GameRng::from_seed(42)));
+        assert_ne!(layout, gem_layout(&mut GameRng::from_seed(43)));
+
+        // and again for each run after the first
+        let mut world = World::new();
+        insert_run_resources(&mut world, GameMode::Seeded(42));
+        for _ in 0..2 {
+            world.run_system_once(cleanup_run).unwrap();
+            let layout_this_run = gem_layout(&mut world.resource_mut::<GameRng>());
+            assert_eq!(layout_this_run, layout);
+        }
+    }
+
+    #[test]
+    fn quitting_to_the_menu_clears_the_run() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_state(GameState::Playing)
+            .add_systems(OnEnter(GameState::MainMenu), cleanup_run);
+        insert_run_resources(app.world_mut(), GameMode::Endless);
+        app.update();
+        for i in 0..5 {
+            let position = Vec3::new(i as f32 * GEM_SIZE * 2.0, 0.0, 0.0);
+            app.world_mut()
+                .spawn((Gem, RunEntity, Transform::from_translation(position)));
+        }
+
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::MainMenu);
+        app.update();
+
+        let world = app.world_mut();
+        let gems = world.query_filtered::<(), With<Gem>>().iter(world).count();
+        assert_eq!(gems, 0);
+    }
+
+    #[test]
+    fn restart_is_ignored_until_the_game_over_delay_ends() {
+        let mut world = World::new();
+        let mut keyboard_input = ButtonInput::<KeyCode>::default();
+        keyboard_input.press(KeyCode::KeyR);
+        world.insert_resource(keyboard_input);
+        world.init_resource::<Events<MenuActivated>>();
+        world.insert_resource(GameOverDelay(Timer::from_seconds(1.0, TimerMode::Once)));
+        world.insert_resource(GameRng::from_seed(1));
+        world.insert_resource(Score(10));
+        world.init_resource::<Stats>();
+        world.init_resource::<GameMode>();
+        world.init_non_send_resource::<share::SystemClipboard>();
+        world.init_resource::<NextState<GameState>>();
+
+        world.run_system_once(handle_game_over_input).unwrap();
+        assert!(matches!(
+            *world.resource::<NextState<GameState>>(),
+            NextState::Unchanged
+        ));
+
+        world
+            .resource_mut::<GameOverDelay>()
+            .tick(Duration::from_secs(1));
+        world.run_system_once(handle_game_over_input).unwrap();
+        assert!(matches!(
+            *world.resource::<NextState<GameState>>(),
+            NextState::Pending(GameState::Playing)
+        ));
+    }
+
+    #[test]
+    fn inverted_vertical_moves_the_rug_down_on_up() {
+        let mut world = World::new();
+        let mut keyboard_input = ButtonInput::<KeyCode>::default();
+        keyboard_input.press(PlayerId(0).controls().up);
+        world.insert_resource(keyboard_input);
+        let mut settings = GameSettings::default();
+        settings.invert_vertical = true;
+        world.insert_resource(settings);
+        world.init_resource::<ScrollFreeze>();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_millis(100));
+        world.insert_resource(time);
+        let player = world
+            .spawn((
+                Player,
+                PlayerId(0),
+                Transform::default(),
+                Velocity::default(),
+                Throttle::default(),
+            ))
+            .id();
+
+        world.run_system_once(move_player).unwrap();
+
+        assert!(world.get::<Transform>(player).unwrap().translation.y < 0.0);
+    }
+
+    /// Fixed ticks a gameplay system ran on
+    #[derive(Resource, Default)]
+    struct Ticks(usize);
+
+    /// Gameplay system that, like many, assumes the player is there
+    fn needs_player(players: Query<&Transform, With<Player>>, mut ticks: ResMut<Ticks>) {
+        let _player = players.single();
+        ticks.0 += 1;
+    }
+
+    #[test]
+    fn gameplay_waits_for_a_respawned_player() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_state(GameState::Playing)
+            .init_resource::<Ticks>()
+            .configure_sets(FixedUpdate, gameplay_sets())
+            .add_systems(FixedUpdate, needs_player.in_set(GameplaySet::Movement));
+
+        // between the old run being cleaned up and the new one spawning
+        app.world_mut().run_schedule(FixedUpdate);
+        assert_eq!(app.world().resource::<Ticks>().0, 0);
+
+        app.world_mut().spawn((Player, Transform::default()));
+        app.world_mut().run_schedule(FixedUpdate);
+        assert_eq!(app.world().resource::<Ticks>().0, 1);
+    }
+
+    #[test]
+    fn later_gems_score_higher() {
+        let early = Stats::default();
+        let late = Stats {
+            distance: 10_000.0,
+            ..default()
+        };
+        assert!(late.distance_bonus() > early.distance_bonus());
+
+        let far = Stats {
+            distance: f32::MAX,
+            ..default()
+        };
+        assert_eq!(far.distance_bonus(), MAX_DISTANCE_BONUS);
+    }
+
+    #[test]
+    fn overlapping_pickups_sort_the_same_way_every_time() {
+        let (first, second, far) = (
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+            Entity::from_raw(3),
+        );
+        let mut picked = vec![(20.0, far, 'c'), (5.0, second, 'b'), (5.0, first, 'a')];
+        let mut reversed: Vec<_> = picked.iter().rev().copied().collect();
+
+        sort_pickups(&mut picked);
+        sort_pickups(&mut reversed);
+
+        let order: Vec<char> = picked.iter().map(|(_, _, gem)| *gem).collect();
+        assert_eq!(order, ['a', 'b', 'c']);
+        assert_eq!(picked, reversed);
+    }
+
+    #[test]
+    fn abbreviates_scores_at_the_unit_boundaries() {
+        let cases = [
+            (999, "999"),
+            (1_000, "1,000"),
+            (9_999, "9,999"),
+            (10_000, "10.0K"),
+            (999_949, "999.9K"),
+            (999_950, "1.0M"),
+            (usize::MAX, "18,446,744.1T"),
+        ];
+        for (score, expected) in cases {
+            assert_eq!(format_score(score, ScoreFormat::Abbreviated), expected);
+        }
+    }
+
+    #[test]
+    fn hud_skips_a_frame_without_its_entities() {
+        let mut app = App::new();
+        // the text plugin sets up what `TextUiWriter` needs
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), TextPlugin))
+            .init_asset::<Image>()
+            .init_asset::<TextureAtlasLayout>()
+            .insert_resource(State::new(GameState::GameOver))
+            .insert_resource(GameRng::from_seed(1))
+            .insert_resource(GameSettings::default())
+            .insert_resource(Score(10))
+            .init_resource::<GameMode>()
+            .init_resource::<Stats>()
+            .init_resource::<Fuel>()
+            .init_resource::<ScoreInFlight>()
+            .insert_resource(Stamina(MAX_STAMINA / 2.0))
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_event::<HealthChanged>()
+            .add_systems(
+                Update,
+                (
+                    show_game_over,
+                    update_health_ui,
+                    update_scoreboard,
+                    (read_health_changes, tween_health_bar).chain(),
+                    update_stamina_ui,
+                    cycle_health_display,
+                    follow_player,
+                ),
+            );
+
+        // a player despawned mid-respawn, with the HUD not spawned yet
+        let player = app
+            .world_mut()
+            .spawn((
+                Player,
+                PlayerId(0),
+                Health {
+                    current: 0,
+                    max: MAX_HEALTH,
+                    partial: 0.0,
+                },
+            ))
+            .id();
+        app.world_mut().despawn(player);
+        app.world_mut()
+            .send_event(HealthChanged { current: 0, max: 0 });
+
+        app.update();
+
+        let world = app.world_mut();
+        assert_eq!(**world.resource::<Score>(), 10);
+        assert_eq!(**world.resource::<Stamina>(), MAX_STAMINA / 2.0);
+        assert_eq!(*world.resource::<State<GameState>>(), GameState::GameOver);
+        assert_eq!(world.query::<&Node>().iter(world).count(), 0);
+    }
+
+    #[test]
+    fn camera_stops_at_the_edge_of_the_level() {
+        let mut world = World::new();
+        let mut settings = GameSettings::default();
+        settings.camera_bounds = Some(CameraBounds {
+            min_x: 0.0,
+            max_x: 2000.0,
+        });
+        world.insert_resource(settings);
+        world.init_resource::<Time>();
+        let mut projection = OrthographicProjection::default_2d();
+        projection.area = Rect::new(-400.0, -300.0, 400.0, 300.0);
+        let camera = world
+            .spawn((MainCamera, Transform::default(), projection))
+            .id();
+        let player = world
+            .spawn((
+                Player,
+                Transform::from_xyz(5000.0, 0.0, 0.0),
+                Velocity::default(),
+            ))
+            .id();
+
+        world.run_system_once(follow_player).unwrap();
+        assert_eq!(
+            world.get::<Transform>(camera).unwrap().translation.x,
+            1600.0
+        );
+
+        world.get_mut::<Transform>(player).unwrap().translation.x = -300.0;
+        world.run_system_once(follow_player).unwrap();
+        assert_eq!(world.get::<Transform>(camera).unwrap().translation.x, 400.0);
+    }
+
+    /// Run a frame, returning the state changes it sent
+    fn state_changes(app: &mut App) -> Vec<(Option<GameState>, GameState)> {
+        app.update();
+        app.world_mut()
+            .resource_mut::<Events<GameStateChanged>>()
+            .drain()
+            .map(|change| (change.from, change.to))
+            .collect()
+    }
+
+    #[test]
+    fn one_transition_sends_one_state_change() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_state(GameState::MainMenu)
+            .add_event::<GameStateChanged>()
+            .add_systems(Update, emit_state_changes);
+        assert_eq!(state_changes(&mut app), [(None, GameState::MainMenu)]);
+
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        assert_eq!(
+            state_changes(&mut app),
+            [(Some(GameState::MainMenu), GameState::Playing)]
+        );
+        assert_eq!(state_changes(&mut app), []);
+
+        // re-entering the same state isn't a change
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        assert_eq!(state_changes(&mut app), []);
+    }
 }
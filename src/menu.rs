@@ -0,0 +1,766 @@
+use bevy::{
+    input::keyboard::{Key, KeyboardInput},
+    prelude::*,
+};
+
+use crate::{
+    navigation::{navigate_menus, MenuActivated, MenuAdjusted, MenuBack, MenuItem, Selected},
+    save::SaveData,
+    settings::{AssistMode, DeathCondition, DifficultyPreset, GameSettings, VolumeLevels},
+    GameMode, GameRng, GameState, HudText, MAX_DEADZONE, TEXT_COLOR,
+};
+
+const OVERLAY_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.5);
+/// How much one left/right press changes a volume
+const VOLUME_STEP: f32 = 0.1;
+/// How much one left/right press changes the stick deadzone
+const DEADZONE_STEP: f32 = 0.05;
+/// How much one left/right press changes the brake's minimum speed
+const BRAKE_STEP: f32 = 0.1;
+
+/// Plugin for the main menu, the settings screen and the pause overlay
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SeedInput>()
+            .add_systems(OnEnter(GameState::MainMenu), spawn_main_menu)
+            .add_systems(OnEnter(GameState::Paused), spawn_pause_overlay)
+            .add_systems(OnEnter(GameState::Settings), spawn_settings_menu)
+            .add_systems(OnExit(GameState::Settings), save_settings)
+            .add_systems(
+                Update,
+                (
+                    (
+                        // the seed prompt goes first, so the key that opens
+                        // it isn't also read as typed into it
+                        handle_seed_input,
+                        handle_main_menu_input,
+                        update_seed_ui,
+                        update_assist_ui,
+                        update_coop_ui,
+                    )
+                        .chain()
+                        .after(navigate_menus)
+                        .run_if(in_state(GameState::MainMenu)),
+                    pause_game.run_if(in_state(GameState::Playing)),
+                    (handle_pause_input, update_volume_rows, update_invert_ui)
+                        .chain()
+                        .after(navigate_menus)
+                        .run_if(in_state(GameState::Paused)),
+                    (
+                        handle_settings_input,
+                        update_volume_rows,
+                        update_settings_rows,
+                    )
+                        .chain()
+                        .after(navigate_menus)
+                        .run_if(in_state(GameState::Settings)),
+                ),
+            );
+    }
+}
+
+/// Digits typed into the main menu's seed prompt, while it is open
+#[derive(Resource, Default)]
+pub struct SeedInput(Option<String>);
+
+/// Row of the pause menu that adjusts one of the volumes
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum VolumeRow {
+    Master,
+    Music,
+    Sfx,
+}
+
+impl VolumeRow {
+    const ALL: [VolumeRow; 3] = [VolumeRow::Master, VolumeRow::Music, VolumeRow::Sfx];
+
+    fn label(self) -> &'static str {
+        match self {
+            VolumeRow::Master => "Master volume",
+            VolumeRow::Music => "Music volume",
+            VolumeRow::Sfx => "Effects volume",
+        }
+    }
+
+    fn volume(self, volumes: &VolumeLevels) -> f32 {
+        match self {
+            VolumeRow::Master => volumes.master.0,
+            VolumeRow::Music => volumes.music.0,
+            VolumeRow::Sfx => volumes.sfx.0,
+        }
+    }
+
+    fn volume_mut<'a>(self, volumes: &'a mut VolumeLevels) -> &'a mut f32 {
+        match self {
+            VolumeRow::Master => &mut volumes.master.0,
+            VolumeRow::Music => &mut volumes.music.0,
+            VolumeRow::Sfx => &mut volumes.sfx.0,
+        }
+    }
+}
+
+/// What a main menu row does when activated
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum MainMenuAction {
+    Start,
+    Daily,
+    TimeAttack,
+    Seed,
+    Settings,
+    Assist,
+    Coop,
+}
+
+impl MainMenuAction {
+    fn label(self) -> &'static str {
+        match self {
+            MainMenuAction::Start => "Start",
+            MainMenuAction::Daily => "D: daily challenge",
+            MainMenuAction::TimeAttack => "T: time attack",
+            MainMenuAction::Seed => "S: play a seed",
+            MainMenuAction::Settings => "O: settings",
+            MainMenuAction::Assist | MainMenuAction::Coop => "",
+        }
+    }
+
+    /// Shortcut key that picks the entry without selecting it first
+    fn hotkey(self) -> Option<KeyCode> {
+        match self {
+            MainMenuAction::Start => None,
+            MainMenuAction::Daily => Some(KeyCode::KeyD),
+            MainMenuAction::TimeAttack => Some(KeyCode::KeyT),
+            MainMenuAction::Seed => Some(KeyCode::KeyS),
+            MainMenuAction::Settings => Some(KeyCode::KeyO),
+            MainMenuAction::Assist => Some(KeyCode::KeyA),
+            MainMenuAction::Coop => Some(KeyCode::KeyC),
+        }
+    }
+}
+
+const MAIN_MENU_ACTIONS: [MainMenuAction; 7] = [
+    MainMenuAction::Start,
+    MainMenuAction::Daily,
+    MainMenuAction::TimeAttack,
+    MainMenuAction::Seed,
+    MainMenuAction::Settings,
+    MainMenuAction::Assist,
+    MainMenuAction::Coop,
+];
+
+/// What a pause menu row does when activated. Volume rows are adjusted with
+/// left and right instead.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseAction {
+    Resume,
+    InvertVertical,
+    Quit,
+}
+
+#[derive(Component)]
+struct InvertVerticalUi;
+
+/// Row of the settings screen, after the volume rows. Rows holding a value
+/// are adjusted with left and right, and toggles flip on Enter too.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum SettingsRow {
+    InvertVertical,
+    Deadzone,
+    BrakeSpeed,
+    Assist,
+    Difficulty,
+    GraphicsQuality,
+    DeathCondition,
+    HealthDisplay,
+    ScoreFormat,
+    Back,
+}
+
+impl SettingsRow {
+    const ALL: [SettingsRow; 10] = [
+        SettingsRow::InvertVertical,
+        SettingsRow::Deadzone,
+        SettingsRow::BrakeSpeed,
+        SettingsRow::Assist,
+        SettingsRow::Difficulty,
+        SettingsRow::GraphicsQuality,
+        SettingsRow::DeathCondition,
+        SettingsRow::HealthDisplay,
+        SettingsRow::ScoreFormat,
+        SettingsRow::Back,
+    ];
+
+    fn label(self, settings: &GameSettings) -> String {
+        let on_off = |on| if on { "on" } else { "off" };
+        match self {
+            SettingsRow::InvertVertical => {
+                format!("Invert up/down: {}", on_off(settings.invert_vertical))
+            }
+            SettingsRow::Deadzone => format!("Stick deadzone: {:.0}%", settings.deadzone * 100.0),
+            SettingsRow::BrakeSpeed => {
+                format!("Brake speed: {:.0}%", settings.brake_min_speed * 100.0)
+            }
+            SettingsRow::Assist => format!(
+                "Assist mode: {}",
+                on_off(settings.assist_mode == AssistMode::On)
+            ),
+            SettingsRow::Difficulty => {
+                match DifficultyPreset::matching(&settings.difficulty_curve) {
+                    Some(preset) => format!("Difficulty: {preset:?}"),
+                    None => "Difficulty: Custom".to_string(),
+                }
+            }
+            SettingsRow::GraphicsQuality => {
+                format!("Graphics quality: {:?}", settings.graphics_quality)
+            }
+            SettingsRow::DeathCondition => match settings.death_condition {
+                DeathCondition::Health => "Run ends: out of health".to_string(),
+                DeathCondition::Fuel => "Run ends: out of health or fuel".to_string(),
+                DeathCondition::Timer(limit) => {
+                    format!("Run ends: out of health or after {limit:.0}s")
+                }
+            },
+            SettingsRow::HealthDisplay => {
+                format!("Health display: {:?}", settings.health_display)
+            }
+            SettingsRow::ScoreFormat => format!("Score format: {:?}", settings.score_format),
+            SettingsRow::Back => "Esc: back".to_string(),
+        }
+    }
+
+    /// Change the row's setting by `step` presses of right, or left for a
+    /// negative step
+    fn adjust(self, settings: &mut GameSettings, step: isize) {
+        let step_f32 = step as f32;
+        match self {
+            SettingsRow::InvertVertical => settings.invert_vertical = !settings.invert_vertical,
+            SettingsRow::Deadzone => {
+                settings.deadzone =
+                    (settings.deadzone + step_f32 * DEADZONE_STEP).clamp(0.0, MAX_DEADZONE);
+            }
+            SettingsRow::BrakeSpeed => {
+                settings.brake_min_speed =
+                    (settings.brake_min_speed + step_f32 * BRAKE_STEP).clamp(0.0, 1.0);
+            }
+            SettingsRow::Assist => settings.assist_mode = settings.assist_mode.toggled(),
+            SettingsRow::Difficulty => {
+                settings.difficulty_curve =
+                    DifficultyPreset::cycled(&settings.difficulty_curve, step).curve();
+            }
+            SettingsRow::GraphicsQuality => {
+                settings.graphics_quality = settings.graphics_quality.cycled(step);
+            }
+            SettingsRow::DeathCondition => {
+                settings.death_condition = settings.death_condition.cycled(step);
+            }
+            SettingsRow::HealthDisplay => {
+                settings.health_display = settings.health_display.cycled(step);
+            }
+            SettingsRow::ScoreFormat => settings.score_format = settings.score_format.cycled(step),
+            SettingsRow::Back => (),
+        }
+    }
+}
+
+/// Root of the main menu overlay
+#[derive(Component)]
+pub struct MainMenuUi;
+
+#[derive(Component)]
+struct SeedUi;
+
+#[derive(Component)]
+struct AssistUi;
+
+#[derive(Component)]
+struct CoopUi;
+
+/// Spawn a full screen, centered column of text that is removed when leaving
+/// `state`
+fn spawn_overlay(
+    commands: &mut Commands,
+    state: GameState,
+    lines: &[&str],
+    background: Color,
+) -> Entity {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(background),
+            StateScoped(state),
+        ))
+        .with_children(|p| {
+            for (i, line) in lines.iter().enumerate() {
+                // the first line is the title
+                let size = if i == 0 { 2.0 } else { 1.0 };
+                p.spawn((
+                    Text::new(*line),
+                    TextFont::default(),
+                    HudText(size),
+                    TextColor(TEXT_COLOR),
+                ));
+            }
+        })
+        .id()
+}
+
+/// Spawn a selectable text row into `menu`, along with `extra`
+pub fn spawn_menu_item(
+    commands: &mut Commands,
+    menu: Entity,
+    order: usize,
+    label: &str,
+    extra: impl Bundle,
+) -> Entity {
+    let item = commands
+        .spawn((
+            Text::new(label),
+            TextFont::default(),
+            HudText(1.0),
+            TextColor(TEXT_COLOR),
+            MenuItem(order),
+            extra,
+        ))
+        .id();
+    commands.entity(menu).add_child(item);
+    item
+}
+
+pub fn spawn_main_menu(mut commands: Commands) {
+    let menu = spawn_overlay(
+        &mut commands,
+        GameState::MainMenu,
+        &["MAGIC RUG"],
+        Color::NONE,
+    );
+    commands.entity(menu).insert(MainMenuUi);
+
+    for (order, action) in MAIN_MENU_ACTIONS.into_iter().enumerate() {
+        let item = spawn_menu_item(&mut commands, menu, order, action.label(), action);
+        // rows with a changing label are filled in by their own systems
+        match action {
+            MainMenuAction::Seed => {
+                commands.entity(item).insert(SeedUi);
+            }
+            MainMenuAction::Assist => {
+                commands.entity(item).insert(AssistUi);
+            }
+            MainMenuAction::Coop => {
+                commands.entity(item).insert(CoopUi);
+            }
+            MainMenuAction::Start
+            | MainMenuAction::Daily
+            | MainMenuAction::TimeAttack
+            | MainMenuAction::Settings => (),
+        }
+    }
+}
+
+fn spawn_pause_overlay(mut commands: Commands) {
+    let overlay = spawn_overlay(
+        &mut commands,
+        GameState::Paused,
+        &["PAUSED", "Up/Down: select  Left/Right: adjust"],
+        OVERLAY_COLOR,
+    );
+
+    spawn_menu_item(
+        &mut commands,
+        overlay,
+        0,
+        "Esc: resume",
+        PauseAction::Resume,
+    );
+    for (i, row) in VolumeRow::ALL.into_iter().enumerate() {
+        spawn_menu_item(&mut commands, overlay, i + 1, "", row);
+    }
+    let invert = spawn_menu_item(
+        &mut commands,
+        overlay,
+        VolumeRow::ALL.len() + 1,
+        "",
+        PauseAction::InvertVertical,
+    );
+    commands.entity(invert).insert(InvertVerticalUi);
+    spawn_menu_item(
+        &mut commands,
+        overlay,
+        VolumeRow::ALL.len() + 2,
+        "Q: quit to menu",
+        PauseAction::Quit,
+    );
+}
+
+fn spawn_settings_menu(mut commands: Commands) {
+    let overlay = spawn_overlay(
+        &mut commands,
+        GameState::Settings,
+        &["SETTINGS", "Up/Down: select  Left/Right: adjust"],
+        OVERLAY_COLOR,
+    );
+
+    for (i, row) in VolumeRow::ALL.into_iter().enumerate() {
+        spawn_menu_item(&mut commands, overlay, i, "", row);
+    }
+    for (i, row) in SettingsRow::ALL.into_iter().enumerate() {
+        spawn_menu_item(&mut commands, overlay, VolumeRow::ALL.len() + i, "", row);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_main_menu_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut activated: EventReader<MenuActivated>,
+    actions: Query<&MainMenuAction>,
+    save: Res<SaveData>,
+    mut settings: ResMut<GameSettings>,
+    mut seed_input: ResMut<SeedInput>,
+    mut mode: ResMut<GameMode>,
+    mut rng: ResMut<GameRng>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // the seed prompt takes all keyboard input while it's open, including
+    // the Enter that closes it
+    if seed_input.0.is_some() || seed_input.is_changed() {
+        activated.clear();
+        return;
+    }
+
+    let hotkey = MAIN_MENU_ACTIONS.into_iter().find(|action| {
+        action
+            .hotkey()
+            .is_some_and(|key| keyboard_input.just_pressed(key))
+    });
+    let Some(action) = activated
+        .read()
+        .find_map(|event| actions.get(event.0).ok().copied())
+        .or(hotkey)
+    else {
+        return;
+    };
+
+    let selected = match action {
+        MainMenuAction::Start => GameMode::Endless,
+        MainMenuAction::Daily => GameMode::Daily,
+        MainMenuAction::TimeAttack => GameMode::TimeAttack(None),
+        MainMenuAction::Seed => {
+            // start from the last run's seed so it's easy to retry
+            seed_input.0 = Some(
+                save.last_seed
+                    .map(|seed| seed.to_string())
+                    .unwrap_or_default(),
+            );
+            return;
+        }
+        MainMenuAction::Settings => {
+            next_state.set(GameState::Settings);
+            return;
+        }
+        MainMenuAction::Assist => {
+            settings.assist_mode = settings.assist_mode.toggled();
+            settings.save();
+            return;
+        }
+        MainMenuAction::Coop => {
+            settings.coop = !settings.coop;
+            settings.save();
+            return;
+        }
+    };
+
+    *mode = selected;
+    *rng = selected.run_rng();
+    next_state.set(GameState::Playing);
+}
+
+/// Type a seed into the open prompt and start a run with it on Enter
+fn handle_seed_input(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut seed_input: ResMut<SeedInput>,
+    mut mode: ResMut<GameMode>,
+    mut rng: ResMut<GameRng>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if seed_input.0.is_none() {
+        keyboard_events.clear();
+        return;
+    }
+
+    for event in keyboard_events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        let Some(digits) = seed_input.0.as_mut() else {
+            return;
+        };
+
+        match &event.logical_key {
+            Key::Character(typed) if typed.chars().all(|c| c.is_ascii_digit()) => {
+                digits.push_str(typed);
+            }
+            Key::Backspace => {
+                digits.pop();
+            }
+            Key::Escape => seed_input.0 = None,
+            Key::Enter => {
+                if let Ok(seed) = digits.parse() {
+                    *mode = GameMode::Seeded(seed);
+                    *rng = mode.run_rng();
+                    next_state.set(GameState::Playing);
+                }
+                seed_input.0 = None;
+            }
+            _ => (),
+        }
+    }
+}
+
+fn update_seed_ui(seed_input: Res<SeedInput>, mut seed_text: Single<(&mut Text, Ref<SeedUi>)>) {
+    let (ref mut text, ref seed_ui) = *seed_text;
+    if !seed_input.is_changed() && !seed_ui.is_added() {
+        return;
+    }
+
+    text.0 = match &seed_input.0 {
+        Some(digits) => format!("Seed: {digits}_ (Enter: play, Esc: cancel)"),
+        None => "S: play a seed".to_string(),
+    };
+}
+
+fn update_assist_ui(
+    settings: Res<GameSettings>,
+    mut assist_text: Single<(&mut Text, Ref<AssistUi>)>,
+) {
+    let (ref mut text, ref assist_ui) = *assist_text;
+    if !settings.is_changed() && !assist_ui.is_added() {
+        return;
+    }
+
+    text.0 = match settings.assist_mode {
+        AssistMode::Off => "A: assist mode (off)",
+        AssistMode::On => "A: assist mode (on, runs aren't ranked)",
+    }
+    .to_string();
+}
+
+fn update_coop_ui(settings: Res<GameSettings>, mut coop_text: Single<(&mut Text, Ref<CoopUi>)>) {
+    let (ref mut text, ref coop_ui) = *coop_text;
+    if !settings.is_changed() && !coop_ui.is_added() {
+        return;
+    }
+
+    text.0 = if settings.coop {
+        "C: co-op (on, second rug on W/S/E)"
+    } else {
+        "C: co-op (off)"
+    }
+    .to_string();
+}
+
+fn pause_game(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let start = gamepads
+        .iter()
+        .any(|gamepad| gamepad.just_pressed(GamepadButton::Start));
+    if keyboard_input.just_pressed(KeyCode::Escape) || start {
+        next_state.set(GameState::Paused);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_pause_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut activated: EventReader<MenuActivated>,
+    mut adjusted: EventReader<MenuAdjusted>,
+    mut back: EventReader<MenuBack>,
+    actions: Query<&PauseAction>,
+    selected_volume: Option<Single<&VolumeRow, With<Selected>>>,
+    mut settings: ResMut<GameSettings>,
+    mut volumes: VolumeLevels,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let activated = activated
+        .read()
+        .find_map(|event| actions.get(event.0).ok().copied());
+    // Start toggles the pause, like Escape
+    let start = gamepads
+        .iter()
+        .any(|gamepad| gamepad.just_pressed(GamepadButton::Start));
+    if back.read().count() > 0 || start || activated == Some(PauseAction::Resume) {
+        next_state.set(GameState::Playing);
+    } else if keyboard_input.just_pressed(KeyCode::KeyQ) || activated == Some(PauseAction::Quit) {
+        // Leaving for the menu runs the same cleanup as a restart
+        next_state.set(GameState::MainMenu);
+    } else if activated == Some(PauseAction::InvertVertical) {
+        settings.invert_vertical = !settings.invert_vertical;
+        settings.save();
+    }
+
+    let step = adjusted.read().last().map(|adjusted| adjusted.0);
+    let (Some(row), Some(step)) = (selected_volume, step) else {
+        return;
+    };
+    // saved by the settings once it has changed
+    let volume = row.volume_mut(&mut volumes);
+    *volume = (*volume + step as f32 * VOLUME_STEP).clamp(0.0, 1.0);
+}
+
+/// Change the selected setting, or go back to the main menu. Changes are
+/// saved once the screen is left.
+#[allow(clippy::too_many_arguments)]
+fn handle_settings_input(
+    mut activated: EventReader<MenuActivated>,
+    mut adjusted: EventReader<MenuAdjusted>,
+    mut back: EventReader<MenuBack>,
+    rows: Query<&SettingsRow>,
+    selected_volume: Option<Single<&VolumeRow, With<Selected>>>,
+    selected_row: Option<Single<&SettingsRow, With<Selected>>>,
+    mut settings: ResMut<GameSettings>,
+    mut volumes: VolumeLevels,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let activated = activated
+        .read()
+        .find_map(|event| rows.get(event.0).ok().copied());
+    if back.read().count() > 0 || activated == Some(SettingsRow::Back) {
+        next_state.set(GameState::MainMenu);
+        return;
+    }
+    if let Some(row) = activated {
+        row.adjust(&mut settings, 1);
+    }
+
+    let Some(step) = adjusted.read().last().map(|adjusted| adjusted.0) else {
+        return;
+    };
+    if let Some(row) = selected_volume {
+        let volume = row.volume_mut(&mut volumes);
+        *volume = (*volume + step as f32 * VOLUME_STEP).clamp(0.0, 1.0);
+    } else if let Some(row) = selected_row {
+        row.adjust(&mut settings, step);
+    }
+}
+
+/// Save the settings, and pick up the difficulty they ask for
+fn save_settings(mut commands: Commands, settings: Res<GameSettings>) {
+    commands.insert_resource(settings.difficulty_curve.clone());
+    settings.save();
+}
+
+fn update_volume_rows(volumes: VolumeLevels, mut rows: Query<(Ref<VolumeRow>, &mut Text)>) {
+    let changed =
+        volumes.master.is_changed() || volumes.music.is_changed() || volumes.sfx.is_changed();
+    for (row, mut text) in &mut rows {
+        if !changed && !row.is_added() {
+            continue;
+        }
+
+        text.0 = format!("{}: {:.0}%", row.label(), row.volume(&volumes) * 100.0);
+    }
+}
+
+fn update_settings_rows(
+    settings: Res<GameSettings>,
+    mut rows: Query<(Ref<SettingsRow>, &mut Text)>,
+) {
+    for (row, mut text) in &mut rows {
+        if !settings.is_changed() && !row.is_added() {
+            continue;
+        }
+
+        text.0 = row.label(&settings);
+    }
+}
+
+fn update_invert_ui(
+    settings: Res<GameSettings>,
+    mut invert_text: Single<(&mut Text, Ref<InvertVerticalUi>)>,
+) {
+    let (ref mut text, ref invert_ui) = *invert_text;
+    if !settings.is_changed() && !invert_ui.is_added() {
+        return;
+    }
+
+    text.0 = SettingsRow::InvertVertical.label(&settings);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::settings::{MasterVolume, MusicVolume, SfxVolume};
+
+    #[test]
+    fn difficulty_row_steps_through_the_presets() {
+        let mut settings = GameSettings::default();
+        assert_eq!(
+            SettingsRow::Difficulty.label(&settings),
+            "Difficulty: Normal"
+        );
+
+        SettingsRow::Difficulty.adjust(&mut settings, 1);
+        assert_eq!(settings.difficulty_curve, DifficultyPreset::Hard.curve());
+        assert_eq!(SettingsRow::Difficulty.label(&settings), "Difficulty: Hard");
+
+        settings.difficulty_curve.0.push((900.0, 20.0));
+        assert_eq!(
+            SettingsRow::Difficulty.label(&settings),
+            "Difficulty: Custom"
+        );
+    }
+
+    #[test]
+    fn pause_menu_invert_row_matches_the_settings_screen() {
+        let mut world = World::new();
+        let mut settings = GameSettings::default();
+        settings.invert_vertical = true;
+        world.insert_resource(settings);
+        let row = world.spawn((Text::default(), InvertVerticalUi)).id();
+
+        world.run_system_once(update_invert_ui).unwrap();
+
+        let settings = world.resource::<GameSettings>();
+        assert_eq!(
+            world.get::<Text>(row).unwrap().0,
+            SettingsRow::InvertVertical.label(settings)
+        );
+    }
+
+    #[test]
+    fn gamepad_start_resumes_from_the_pause_menu() {
+        let mut world = World::new();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.init_resource::<Events<MenuActivated>>();
+        world.init_resource::<Events<MenuAdjusted>>();
+        world.init_resource::<Events<MenuBack>>();
+        world.init_resource::<NextState<GameState>>();
+        world.insert_resource(GameSettings::default());
+        world.insert_resource(MasterVolume(1.0));
+        world.insert_resource(MusicVolume(1.0));
+        world.insert_resource(SfxVolume(1.0));
+        let mut gamepad = Gamepad::default();
+        gamepad.digital_mut().press(GamepadButton::Start);
+        world.spawn(gamepad);
+
+        world.run_system_once(handle_pause_input).unwrap();
+
+        assert!(matches!(
+            *world.resource::<NextState<GameState>>(),
+            NextState::Pending(GameState::Playing)
+        ));
+    }
+}
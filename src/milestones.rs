@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use crate::{settings::Volumes, CollisionSound, ScoreboardUi, TEXT_COLOR};
+
+/// Every this many gems collected, play a fanfare and flash the scoreboard
+pub const GEM_MILESTONE_INTERVAL: usize = 25;
+
+const FLASH_DURATION: f32 = 0.6;
+/// How many times per second the scoreboard toggles its color while flashing
+const FLASH_BLINK_RATE: f32 = 10.0;
+const FLASH_COLOR: Color = Color::srgb(1.0, 0.85, 0.3);
+/// The fanfare reuses the pickup clip, played back faster so it stands out
+const FANFARE_SPEED: f32 = 1.5;
+
+/// Plugin to celebrate gem collection milestones
+pub struct MilestonesPlugin;
+
+impl Plugin for MilestonesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GemMilestone>()
+            .add_systems(Update, (celebrate_milestones, flash_scoreboard).chain());
+    }
+}
+
+/// Sent when the number of gems collected this run hits a multiple of
+/// `GEM_MILESTONE_INTERVAL`
+#[derive(Event)]
+pub struct GemMilestone {
+    pub gems_collected: usize,
+}
+
+#[derive(Component, Deref, DerefMut)]
+struct ScoreboardFlash(Timer);
+
+fn celebrate_milestones(
+    mut commands: Commands,
+    mut milestones: EventReader<GemMilestone>,
+    sound: Res<CollisionSound>,
+    volumes: Volumes,
+    scoreboard: Single<Entity, With<ScoreboardUi>>,
+) {
+    for milestone in milestones.read() {
+        debug!("collected {} gems", milestone.gems_collected);
+
+        commands.spawn((
+            AudioPlayer(sound.clone()),
+            PlaybackSettings::DESPAWN
+                .with_speed(FANFARE_SPEED)
+                .with_volume(volumes.sfx()),
+        ));
+        commands
+            .entity(*scoreboard)
+            .insert(ScoreboardFlash(Timer::from_seconds(
+                FLASH_DURATION,
+                TimerMode::Once,
+            )));
+    }
+}
+
+fn flash_scoreboard(
+    mut commands: Commands,
+    mut scoreboard: Query<(Entity, &mut ScoreboardFlash, &mut TextColor)>,
+    time: Res<Time>,
+) {
+    for (entity, mut flash, mut color) in &mut scoreboard {
+        if flash.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<ScoreboardFlash>();
+            color.0 = TEXT_COLOR;
+            continue;
+        }
+
+        let lit = ((flash.elapsed_secs() * FLASH_BLINK_RATE) as u32).is_multiple_of(2);
+        color.0 = if lit { FLASH_COLOR } else { TEXT_COLOR };
+    }
+}
@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+
+use crate::{
+    collect_gems,
+    gems::GemKind,
+    obstacles::{hurt_player, Invulnerable},
+    settings::GameSettings,
+    GameState, GameplaySet, Gem, Health, Player, GEM_PICKUP_RADIUS,
+};
+
+/// How often a mimic gives itself away, per second
+const FLICKER_RATE: f32 = 0.7;
+/// Fraction of each flicker period the mimic spends dimmed
+const FLICKER_DUTY: f32 = 0.08;
+const FLICKER_ALPHA: f32 = 0.55;
+
+/// Plugin for gems that hurt the player instead of scoring
+pub struct MimicPlugin;
+
+impl Plugin for MimicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            touch_mimics
+                .before(collect_gems)
+                .in_set(GameplaySet::Collision),
+        )
+        .add_systems(Update, flicker_mimics.run_if(in_state(GameState::Playing)));
+    }
+}
+
+/// Hurt a player for flying into a mimic, the same way an obstacle would
+fn touch_mimics(
+    mut commands: Commands,
+    mut players: Query<(Entity, &Transform, &mut Health, Has<Invulnerable>), With<Player>>,
+    gem_query: Query<(Entity, &Transform, &GemKind), With<Gem>>,
+    settings: Res<GameSettings>,
+) {
+    for (player_entity, player_transform, mut health, invulnerable) in &mut players {
+        let player_pos = player_transform.translation.truncate();
+
+        for (gem_entity, transform, kind) in &gem_query {
+            if *kind != GemKind::Mimic
+                || player_pos.distance(transform.translation.truncate()) >= GEM_PICKUP_RADIUS
+            {
+                continue;
+            }
+
+            commands.entity(gem_entity).despawn_recursive();
+            if !invulnerable {
+                let damage = settings.assist_mode.damage_multiplier();
+                hurt_player(&mut commands, player_entity, &mut health, damage);
+            }
+            // the despawn is deferred, so stop before another rug touches it too
+            return;
+        }
+    }
+}
+
+/// Briefly dim mimics now and then, as a tell for observant players. Each
+/// mimic's position offsets its timing so they don't flicker in sync.
+fn flicker_mimics(mut gems: Query<(&mut Sprite, &Transform, &GemKind)>, time: Res<Time>) {
+    for (mut sprite, transform, kind) in &mut gems {
+        if *kind != GemKind::Mimic {
+            continue;
+        }
+
+        let phase = time.elapsed_secs() * FLICKER_RATE + transform.translation.x * 0.01;
+        let alpha = if phase.fract() < FLICKER_DUTY {
+            FLICKER_ALPHA
+        } else {
+            1.0
+        };
+        sprite.color = kind.color().with_alpha(alpha);
+    }
+}
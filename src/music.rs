@@ -0,0 +1,274 @@
+use bevy::{audio::Volume, prelude::*};
+use rand::seq::SliceRandom;
+
+use crate::{
+    emit_state_changes,
+    settings::{GameSettings, PlaylistOrder, Volumes},
+    CollisionEvent, GameState, GameStateChanged,
+};
+
+/// Fraction of the music volume taken away while a pickup sound plays
+const DUCK_AMOUNT: f32 = 0.6;
+/// Seconds for the music to ramp back to full volume after a pickup
+const DUCK_RECOVERY: f32 = 0.5;
+/// Seconds to fade between the menu track and the playlist
+const CROSSFADE_TIME: f32 = 1.5;
+
+/// Plugin for the background music
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TrackStarted>()
+            .init_resource::<MusicMix>()
+            .add_systems(
+                Update,
+                (
+                    follow_game_state,
+                    crossfade_music,
+                    advance_playlist,
+                    duck_music,
+                )
+                    .chain()
+                    .after(emit_state_changes),
+            );
+    }
+}
+
+/// The background music playlist, and how far the music is currently ducked.
+///
+/// The entity lives for the whole session rather than per state, so menus
+/// and runs coming and going don't interrupt the playlist.
+#[derive(Component, Default)]
+pub struct MusicController {
+    tracks: Vec<Handle<AudioSource>>,
+    /// Indices into `tracks`, in the order they are played
+    order: Vec<usize>,
+    /// Position in `order` of the track that is playing
+    position: usize,
+    /// 1 right after a pickup, back to 0 once fully recovered
+    duck: f32,
+    /// How far the playlist is faded out under the menu track, from 0 to 1
+    faded: f32,
+}
+
+/// The settings' menu track, looped on the menus in place of the playlist
+#[derive(Component)]
+struct MenuMusic;
+
+/// How much of the music is the menu track rather than the playlist, from 0
+/// to 1, and where the crossfade is heading
+#[derive(Resource, Default)]
+struct MusicMix {
+    menu: f32,
+    target: f32,
+}
+
+impl MusicController {
+    /// Volume of the playlist, after the crossfade and ducking
+    fn volume(&self, music_volume: Volume) -> Volume {
+        Volume::new(music_volume.get() * (1.0 - self.faded) * (1.0 - DUCK_AMOUNT * self.duck))
+    }
+
+    fn current_track(&self) -> Handle<AudioSource> {
+        self.tracks[self.current_index()].clone()
+    }
+
+    /// Index into the settings' playlist of the track that is playing
+    pub fn current_index(&self) -> usize {
+        self.order[self.position]
+    }
+
+    /// Move on to the next track, starting the playlist over after the last
+    fn advance(&mut self, playlist_order: PlaylistOrder) {
+        self.position += 1;
+        if self.position >= self.order.len() {
+            self.position = 0;
+            self.arrange(playlist_order);
+        }
+    }
+
+    fn arrange(&mut self, playlist_order: PlaylistOrder) {
+        self.order = (0..self.tracks.len()).collect();
+        if playlist_order == PlaylistOrder::Shuffle {
+            // not the run's generator, so music never changes a layout
+            self.order.shuffle(&mut rand::rng());
+        }
+    }
+}
+
+/// Sent when the playlist starts a track from the beginning
+#[derive(Event)]
+pub struct TrackStarted;
+
+fn is_menu(state: GameState) -> bool {
+    matches!(state, GameState::MainMenu | GameState::Settings)
+}
+
+/// Start the music with the first state, hold it while paused and crossfade
+/// between the menu track and the playlist when a run starts or ends
+fn follow_game_state(
+    mut commands: Commands,
+    mut changes: EventReader<GameStateChanged>,
+    music: Option<Single<&AudioSink, With<MusicController>>>,
+    mut mix: ResMut<MusicMix>,
+    asset_server: Res<AssetServer>,
+    settings: Res<GameSettings>,
+    volumes: Volumes,
+) {
+    for change in changes.read() {
+        if change
+            .from
+            .is_some_and(|from| is_menu(from) != is_menu(change.to))
+        {
+            mix.target = if is_menu(change.to) { 1.0 } else { 0.0 };
+        }
+
+        match (change.from, change.to) {
+            (None, to) => start_music(
+                &mut commands,
+                &asset_server,
+                &settings,
+                volumes.music(),
+                &mut mix,
+                to,
+            ),
+            (_, GameState::Paused) => {
+                if let Some(sink) = &music {
+                    sink.pause();
+                }
+            }
+            (Some(GameState::Paused), _) => {
+                if let Some(sink) = &music {
+                    sink.play();
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn start_music(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    settings: &GameSettings,
+    music_volume: Volume,
+    mix: &mut MusicMix,
+    state: GameState,
+) {
+    if !settings.music {
+        return;
+    }
+
+    if let Some(path) = &settings.menu_music {
+        mix.menu = if is_menu(state) { 1.0 } else { 0.0 };
+        mix.target = mix.menu;
+        let volume = music_volume.get() * mix.menu;
+        commands.spawn((
+            AudioPlayer::new(asset_server.load(path)),
+            PlaybackSettings::LOOP.with_volume(Volume::new(volume)),
+            MenuMusic,
+        ));
+    }
+    if settings.music_playlist.is_empty() {
+        return;
+    }
+
+    let mut controller = MusicController {
+        tracks: settings
+            .music_playlist
+            .iter()
+            .map(|path| asset_server.load(path))
+            .collect(),
+        faded: mix.menu,
+        ..default()
+    };
+    controller.arrange(settings.playlist_order);
+
+    commands.spawn((
+        AudioPlayer(controller.current_track()),
+        // the sink is kept once the track ends, so the next one can be queued
+        PlaybackSettings::ONCE.with_volume(controller.volume(music_volume)),
+        controller,
+    ));
+    commands.send_event(TrackStarted);
+}
+
+/// Fade the menu track in and the playlist out, or the other way around,
+/// over `CROSSFADE_TIME`. Without a menu track the playlist plays throughout.
+fn crossfade_music(
+    mut mix: ResMut<MusicMix>,
+    menu_music: Option<Single<&AudioSink, With<MenuMusic>>>,
+    controller: Option<Single<&mut MusicController>>,
+    volumes: Volumes,
+    time: Res<Time>,
+) {
+    let Some(menu_sink) = menu_music else {
+        return;
+    };
+    if mix.menu == mix.target && !volumes.is_changed() {
+        return;
+    }
+
+    let step = time.delta_secs() / CROSSFADE_TIME;
+    mix.menu = if mix.target > mix.menu {
+        (mix.menu + step).min(mix.target)
+    } else {
+        (mix.menu - step).max(mix.target)
+    };
+    menu_sink.set_volume(volumes.music().get() * mix.menu);
+    // the playlist's sink is updated by `duck_music`
+    if let Some(mut controller) = controller {
+        controller.faded = mix.menu;
+    }
+}
+
+/// Start the next track once the current one has finished playing
+fn advance_playlist(
+    mut commands: Commands,
+    music: Option<Single<(Entity, &mut MusicController, &AudioSink)>>,
+    settings: Res<GameSettings>,
+    volumes: Volumes,
+) {
+    let Some(mut music) = music else {
+        return;
+    };
+    let (entity, ref mut controller, sink) = *music;
+    if !sink.empty() {
+        return;
+    }
+
+    controller.advance(settings.playlist_order);
+    // bevy starts playing any player without a sink
+    commands.entity(entity).remove::<AudioSink>().insert((
+        AudioPlayer(controller.current_track()),
+        PlaybackSettings::ONCE.with_volume(controller.volume(volumes.music())),
+    ));
+    commands.send_event(TrackStarted);
+}
+
+/// Lower the music when a gem is collected so the pickup sound stands out,
+/// then ramp it back up. Also picks up changes to the volumes and the
+/// crossfade.
+fn duck_music(
+    mut collisions: EventReader<CollisionEvent>,
+    music: Option<Single<(&mut MusicController, &AudioSink)>>,
+    volumes: Volumes,
+    time: Res<Time>,
+) {
+    let Some(mut music) = music else {
+        collisions.clear();
+        return;
+    };
+    let (ref mut controller, sink) = *music;
+
+    if collisions.read().count() > 0 {
+        controller.duck = 1.0;
+    } else if controller.duck > 0.0 {
+        controller.duck = (controller.duck - time.delta_secs() / DUCK_RECOVERY).max(0.0);
+    } else if !volumes.is_changed() && !controller.is_changed() {
+        return;
+    }
+
+    sink.set_volume(controller.volume(volumes.music()).get());
+}
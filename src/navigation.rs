@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::TEXT_COLOR;
+
+/// Tint of the selected menu row
+const SELECTED_COLOR: Color = Color::srgb(1.0, 0.85, 0.3);
+/// Seconds up or down is held before the selection starts repeating
+const REPEAT_DELAY: f32 = 0.4;
+/// Seconds between moves while the selection repeats
+const REPEAT_INTERVAL: f32 = 0.08;
+/// How far the left stick has to be pushed to count as pressing that way
+const MENU_STICK_THRESHOLD: f32 = 0.5;
+
+/// Plugin for moving through whichever menu is open with the arrow keys or
+/// a gamepad's D-pad or left stick, and activating rows with Enter or A
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MenuActivated>()
+            .add_event::<MenuAdjusted>()
+            .add_event::<MenuBack>()
+            .add_systems(Update, (navigate_menus, highlight_selected).chain());
+    }
+}
+
+/// A row of a menu that can be selected and activated. Menus register their
+/// rows by spawning them with this, and rows are visited in `order`.
+///
+/// Only one menu is expected to be open at a time.
+#[derive(Component)]
+pub struct MenuItem(pub usize);
+
+/// Marks the selected menu row. Menus open with their first row selected.
+#[derive(Component)]
+pub struct Selected;
+
+/// Enter was pressed on a selected menu row. The menu owning the row decides
+/// what that does.
+#[derive(Event)]
+pub struct MenuActivated(pub Entity);
+
+/// Left (-1) or right (1) was pushed on a menu, to adjust the selected row
+#[derive(Event)]
+pub struct MenuAdjusted(pub isize);
+
+/// Escape or B was pressed on a menu, to leave it
+#[derive(Event)]
+pub struct MenuBack;
+
+/// Up or down being held, so the selection keeps moving after a delay
+#[derive(Default)]
+pub struct HeldDirection {
+    /// -1 for up, 1 for down and 0 for neither
+    direction: isize,
+    timer: Timer,
+}
+
+impl HeldDirection {
+    /// How many rows to move this frame: once on the press, then again
+    /// every `REPEAT_INTERVAL` after the `REPEAT_DELAY`
+    fn step(&mut self, direction: isize, delta: Duration) -> isize {
+        if direction != self.direction {
+            self.direction = direction;
+            self.timer = Timer::from_seconds(REPEAT_DELAY, TimerMode::Once);
+            return direction;
+        }
+
+        if direction == 0 || !self.timer.tick(delta).finished() {
+            return 0;
+        }
+        self.timer = Timer::from_seconds(REPEAT_INTERVAL, TimerMode::Once);
+        direction
+    }
+}
+
+/// Keyboard and gamepads are combined before moving, so pushing the same way
+/// on both moves the selection once
+#[allow(clippy::too_many_arguments)]
+pub fn navigate_menus(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    items: Query<(Entity, &MenuItem, Has<Selected>)>,
+    mut activated: EventWriter<MenuActivated>,
+    mut adjusted: EventWriter<MenuAdjusted>,
+    mut back: EventWriter<MenuBack>,
+    mut held: Local<HeldDirection>,
+    mut held_sideways: Local<isize>,
+    // the game clock stops while paused, and the pause menu still repeats
+    time: Res<Time<Real>>,
+) {
+    let mut items: Vec<_> = items.iter().collect();
+    if items.is_empty() {
+        return;
+    }
+    items.sort_by_key(|(_, item, _)| item.0);
+
+    let Some(current) = items.iter().position(|(_, _, selected)| *selected) else {
+        // a menu just opened
+        commands.entity(items[0].0).insert(Selected);
+        return;
+    };
+
+    let gamepad_just_pressed = |button| gamepads.iter().any(|gamepad| gamepad.just_pressed(button));
+    if keyboard_input.just_pressed(KeyCode::Enter) || gamepad_just_pressed(GamepadButton::South) {
+        activated.send(MenuActivated(items[current].0));
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) || gamepad_just_pressed(GamepadButton::East) {
+        back.send(MenuBack);
+    }
+
+    let (sideways, direction) = menu_directions(&keyboard_input, &gamepads);
+    // adjusting doesn't repeat, so a stick has to come back before it counts
+    // again
+    if sideways != *held_sideways {
+        *held_sideways = sideways;
+        if sideways != 0 {
+            adjusted.send(MenuAdjusted(sideways));
+        }
+    }
+
+    let step = held.step(direction, time.delta());
+    if step == 0 {
+        return;
+    }
+
+    let count = items.len() as isize;
+    let next = (current as isize + step).rem_euclid(count) as usize;
+
+    commands.entity(items[current].0).remove::<Selected>();
+    commands.entity(items[next].0).insert(Selected);
+}
+
+/// Which way the arrow keys, D-pads and left sticks are held, as -1 for
+/// left and 1 for right, then -1 for up and 1 for down, each 0 for neither
+fn menu_directions(
+    keyboard_input: &ButtonInput<KeyCode>,
+    gamepads: &Query<&Gamepad>,
+) -> (isize, isize) {
+    let key = |key| {
+        if keyboard_input.pressed(key) {
+            1.0
+        } else {
+            0.0
+        }
+    };
+    let keys = Vec2::new(
+        key(KeyCode::ArrowRight) - key(KeyCode::ArrowLeft),
+        key(KeyCode::ArrowUp) - key(KeyCode::ArrowDown),
+    );
+    let input = gamepads.iter().fold(keys, |input, gamepad| {
+        input + gamepad.dpad() + gamepad.left_stick()
+    });
+
+    let direction = |value: f32| {
+        if value > MENU_STICK_THRESHOLD {
+            1
+        } else if value < -MENU_STICK_THRESHOLD {
+            -1
+        } else {
+            0
+        }
+    };
+    // rows are listed top to bottom
+    (direction(input.x), -direction(input.y))
+}
+
+fn highlight_selected(mut items: Query<(&mut TextColor, Has<Selected>), With<MenuItem>>) {
+    for (mut color, selected) in &mut items {
+        let tint = if selected { SELECTED_COLOR } else { TEXT_COLOR };
+        // only write on a change, so the text isn't marked changed every frame
+        if color.0 != tint {
+            color.0 = tint;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn held_stick_repeats_after_the_delay() {
+        let mut world = World::new();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        let mut gamepad = Gamepad::default();
+        gamepad.analog_mut().set(GamepadAxis::LeftStickY, -0.8);
+        world.spawn(gamepad);
+
+        let (sideways, direction) = world
+            .run_system_once(
+                |keys: Res<ButtonInput<KeyCode>>, gamepads: Query<&Gamepad>| {
+                    menu_directions(&keys, &gamepads)
+                },
+            )
+            .unwrap();
+        assert_eq!((sideways, direction), (0, 1));
+
+        let secs = Duration::from_secs_f32;
+        let mut held = HeldDirection::default();
+        assert_eq!(held.step(direction, Duration::ZERO), 1);
+        assert_eq!(held.step(direction, secs(REPEAT_DELAY - 0.1)), 0);
+        assert_eq!(held.step(direction, secs(0.1)), 1);
+        assert_eq!(held.step(direction, secs(REPEAT_INTERVAL / 2.0)), 0);
+        assert_eq!(held.step(direction, secs(REPEAT_INTERVAL / 2.0)), 1);
+        // letting go and pushing again moves straight away
+        assert_eq!(held.step(0, secs(0.01)), 0);
+        assert_eq!(held.step(direction, secs(0.01)), 1);
+    }
+}
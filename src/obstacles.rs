@@ -0,0 +1,629 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    aabb_overlap, flash,
+    settings::GameSettings,
+    spawn_table::SpawnTable,
+    spawning::{clear_of_obstacle, PLACEMENT_ATTEMPTS},
+    Collider, Difficulty, GameRng, GameState, GameplaySet, Gem, Health, HealthChanged, MainCamera,
+    Player, RunEntity, OBSTACLE_Z, PLAYER_HITBOX, SAFE_LANE_Z, TELEGRAPH_Z,
+};
+
+const OBSTACLE_COLOR: Color = Color::srgb(0.4, 0.2, 0.2);
+const TELEGRAPH_COLOR: Color = Color::srgba(1.0, 0.2, 0.2, 0.5);
+/// Hit points a regular obstacle or wall takes off
+const OBSTACLE_DAMAGE: i32 = 1;
+/// Heavy obstacles hit harder, and are darker and warned about in a
+/// stronger color so they can be told apart
+const HEAVY_OBSTACLE_DAMAGE: i32 = 2;
+const HEAVY_OBSTACLE_COLOR: Color = Color::srgb(0.2, 0.05, 0.05);
+const HEAVY_TELEGRAPH_COLOR: Color = Color::srgba(1.0, 0.0, 0.0, 0.8);
+
+/// How far ahead of the camera center obstacles are placed
+const OBSTACLE_SPAWN_AHEAD: f32 = 500.0;
+
+/// Warning time before an obstacle appears at difficulty 1. Higher
+/// difficulties divide it down to `MIN_TELEGRAPH_DURATION`.
+const TELEGRAPH_DURATION: f32 = 1.0;
+const MIN_TELEGRAPH_DURATION: f32 = 0.3;
+/// How many times per second the telegraph toggles its visibility
+const TELEGRAPH_BLINK_RATE: f32 = 8.0;
+
+const WALL_COLOR: Color = Color::srgb(0.3, 0.15, 0.3);
+const WALL_WIDTH: f32 = 40.0;
+/// Height of each half of a moving wall; tall enough to cover the screen
+const WALL_SEGMENT_HEIGHT: f32 = 600.0;
+/// How far ahead of the camera center walls are placed, just off screen
+const WALL_SPAWN_AHEAD: f32 = 700.0;
+/// Opening between the two halves of a wall at difficulty 1. Higher
+/// difficulties divide it down to `MIN_WALL_GAP`.
+const WALL_GAP: f32 = 260.0;
+const MIN_WALL_GAP: f32 = 140.0;
+/// How far the gap moves up and down from its center
+const WALL_AMPLITUDE: f32 = 150.0;
+/// Angular speed of the wall oscillation, in radians per second
+const WALL_SPEED: f32 = 1.5;
+
+const SAFE_LANE_COLOR: Color = Color::srgba(0.5, 1.0, 0.6, 0.12);
+/// Length of a safe lane along the level
+const SAFE_LANE_LENGTH: f32 = 1200.0;
+/// How far ahead of the camera center safe lanes start, just off screen
+const SAFE_LANE_SPAWN_AHEAD: f32 = 700.0;
+
+/// How long the player can't be hurt again after taking a hit
+const INVULNERABILITY_DURATION: f32 = 1.0;
+
+/// Length of the obstacle-free stretch a run starts in, so the rug isn't
+/// hit before the player has got their bearings
+pub const RESPAWN_SAFE_DISTANCE: f32 = 1500.0;
+
+/// Plugin for spawning obstacles and hurting the player when they hit one
+pub struct ObstaclesPlugin;
+
+impl Plugin for ObstaclesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerHurt>()
+            .init_resource::<ObstacleSpawner>()
+            .init_resource::<WallSpawner>()
+            .init_resource::<SafeLaneSpawner>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    (
+                        spawn_safe_lanes,
+                        spawn_telegraphs,
+                        resolve_telegraphs,
+                        spawn_walls,
+                    )
+                        .chain()
+                        .in_set(GameplaySet::Spawn),
+                    move_walls.in_set(GameplaySet::Movement),
+                    (tick_invulnerability, obstacle_collisions)
+                        .chain()
+                        .in_set(GameplaySet::Collision),
+                ),
+            )
+            .add_systems(
+                Update,
+                blink_telegraphs.run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct Obstacle;
+
+/// Shapes of the obstacles telegraphed ahead of the player. Each hitbox is a
+/// little smaller than its sprite, so brushing the edge of one doesn't hurt.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObstacleKind {
+    Pillar,
+    Boulder,
+    Beam,
+}
+
+impl ObstacleKind {
+    const ALL: [ObstacleKind; 3] = [
+        ObstacleKind::Pillar,
+        ObstacleKind::Boulder,
+        ObstacleKind::Beam,
+    ];
+
+    fn random(rng: &mut impl Rng) -> Self {
+        ObstacleKind::ALL[rng.random_range(0..ObstacleKind::ALL.len())]
+    }
+
+    /// Size the obstacle is drawn at
+    fn sprite_size(self) -> Vec2 {
+        match self {
+            ObstacleKind::Pillar => Vec2::new(40.0, 120.0),
+            ObstacleKind::Boulder => Vec2::new(80.0, 80.0),
+            ObstacleKind::Beam => Vec2::new(160.0, 30.0),
+        }
+    }
+
+    fn hitbox(self) -> Hitbox {
+        Hitbox(match self {
+            ObstacleKind::Pillar => Vec2::new(32.0, 110.0),
+            ObstacleKind::Boulder => Vec2::new(60.0, 60.0),
+            ObstacleKind::Beam => Vec2::new(150.0, 20.0),
+        })
+    }
+}
+
+/// Full size of the box an obstacle hits the player with, independent of
+/// how it is drawn
+#[derive(Component, Debug, Clone, Copy, Deref)]
+pub struct Hitbox(Vec2);
+
+/// Hit points an obstacle takes off the player, before the assist mode
+#[derive(Component, Clone, Copy, Deref)]
+pub struct Damage(pub i32);
+
+impl Damage {
+    fn is_heavy(self) -> bool {
+        self.0 > OBSTACLE_DAMAGE
+    }
+}
+
+/// Warning marker shown where an obstacle is about to appear
+#[derive(Component)]
+pub struct Telegraph {
+    timer: Timer,
+    /// The obstacle that will appear
+    kind: ObstacleKind,
+    damage: Damage,
+}
+
+impl Telegraph {
+    /// Hitbox of the obstacle that will appear
+    pub fn hitbox(&self) -> Hitbox {
+        self.kind.hitbox()
+    }
+}
+
+/// One half of a wall that oscillates up and down, leaving a moving gap
+/// between its top and bottom halves
+#[derive(Component)]
+pub struct MovingWall {
+    /// Center height of this half when the oscillation is at rest
+    base_y: f32,
+    phase: f32,
+}
+
+/// Stretch of the level, from `start` to `end` along x, where no obstacles
+/// spawn so the player gets a breather
+#[derive(Component)]
+pub struct SafeLane {
+    start: f32,
+    end: f32,
+}
+
+impl SafeLane {
+    fn contains(&self, x: f32) -> bool {
+        (self.start..self.end).contains(&x)
+    }
+}
+
+/// Sent whenever a player takes a hit, even one that only adds up a fraction
+/// of a hit point
+#[derive(Event)]
+pub struct PlayerHurt;
+
+/// Short window after a hit during which the player takes no damage
+#[derive(Component, Deref, DerefMut)]
+pub struct Invulnerable(Timer);
+
+impl Invulnerable {
+    pub fn new(duration: f32) -> Self {
+        Invulnerable(Timer::from_seconds(duration, TimerMode::Once))
+    }
+
+    pub fn after_hit() -> Self {
+        Invulnerable::new(INVULNERABILITY_DURATION)
+    }
+}
+
+#[derive(Resource)]
+struct ObstacleSpawner(Timer);
+
+impl FromWorld for ObstacleSpawner {
+    fn from_world(world: &mut World) -> Self {
+        let interval = world.resource::<SpawnTable>().obstacle_interval;
+        ObstacleSpawner(Timer::from_seconds(interval, TimerMode::Repeating))
+    }
+}
+
+#[derive(Resource)]
+struct SafeLaneSpawner(Timer);
+
+impl FromWorld for SafeLaneSpawner {
+    fn from_world(world: &mut World) -> Self {
+        let interval = world.resource::<SpawnTable>().safe_lane_interval;
+        SafeLaneSpawner(Timer::from_seconds(interval, TimerMode::Once))
+    }
+}
+
+#[derive(Resource)]
+struct WallSpawner(Timer);
+
+impl FromWorld for WallSpawner {
+    fn from_world(world: &mut World) -> Self {
+        let interval = world.resource::<SpawnTable>().wall_interval;
+        WallSpawner(Timer::from_seconds(interval, TimerMode::Repeating))
+    }
+}
+
+/// Start the spawn timers over, so a run's obstacles don't pick up where the
+/// last run's left off. Queued by `cleanup_run`.
+pub fn reset_spawners(world: &mut World) {
+    let obstacles = ObstacleSpawner::from_world(world);
+    let walls = WallSpawner::from_world(world);
+    let safe_lanes = SafeLaneSpawner::from_world(world);
+    world.insert_resource(obstacles);
+    world.insert_resource(walls);
+    world.insert_resource(safe_lanes);
+}
+
+/// Mark out a safe lane every `safe_lane_interval` seconds. Lanes come more
+/// often as the difficulty goes up, when breathers are needed most.
+fn spawn_safe_lanes(
+    mut commands: Commands,
+    mut spawner: ResMut<SafeLaneSpawner>,
+    table: Res<SpawnTable>,
+    difficulty: Res<Difficulty>,
+    camera_transform: Single<&Transform, With<MainCamera>>,
+    time: Res<Time>,
+) {
+    if !spawner.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let interval = table.safe_lane_interval / difficulty.sqrt();
+    spawner.0 = Timer::from_seconds(interval, TimerMode::Once);
+
+    let start = camera_transform.translation.x + SAFE_LANE_SPAWN_AHEAD;
+    spawn_safe_lane(&mut commands, start, SAFE_LANE_LENGTH);
+}
+
+/// Mark out a lane `length` long from `start` that no obstacles spawn in
+pub fn spawn_safe_lane(commands: &mut Commands, start: f32, length: f32) {
+    commands.spawn((
+        Sprite::from_color(
+            SAFE_LANE_COLOR,
+            Vec2::new(length, WALL_SEGMENT_HEIGHT * 2.0),
+        ),
+        Transform::from_xyz(start + length / 2.0, 0.0, SAFE_LANE_Z),
+        SafeLane {
+            start,
+            end: start + length,
+        },
+        RunEntity,
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_telegraphs(
+    mut commands: Commands,
+    mut spawner: ResMut<ObstacleSpawner>,
+    spawn_table: Res<SpawnTable>,
+    difficulty: Res<Difficulty>,
+    mut rng: ResMut<GameRng>,
+    camera_transform: Single<&Transform, With<MainCamera>>,
+    safe_lanes: Query<&SafeLane>,
+    gems: Query<&Transform, With<Gem>>,
+    time: Res<Time>,
+) {
+    if !spawner.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let x = camera_transform.translation.x + OBSTACLE_SPAWN_AHEAD;
+    if safe_lanes.iter().any(|lane| lane.contains(x)) {
+        return;
+    }
+    let kind = ObstacleKind::random(&mut rng.rng);
+    // Gems are streamed in further ahead, so steer clear of those already
+    // there
+    let mut y = rng.random::<f32>() * 400.0 - 200.0;
+    for _ in 1..PLACEMENT_ATTEMPTS {
+        let position = Vec2::new(x, y);
+        let clear = gems
+            .iter()
+            .all(|gem| clear_of_obstacle(gem.translation.truncate(), position, *kind.hitbox()));
+        if clear {
+            break;
+        }
+        y = rng.random::<f32>() * 400.0 - 200.0;
+    }
+    let duration = (TELEGRAPH_DURATION / **difficulty).max(MIN_TELEGRAPH_DURATION);
+    let damage = if rng.random::<f32>() < spawn_table.heavy_obstacle_chance {
+        Damage(HEAVY_OBSTACLE_DAMAGE)
+    } else {
+        Damage(OBSTACLE_DAMAGE)
+    };
+    let color = if damage.is_heavy() {
+        HEAVY_TELEGRAPH_COLOR
+    } else {
+        TELEGRAPH_COLOR
+    };
+
+    commands.spawn((
+        Sprite::from_color(color, kind.sprite_size()),
+        Transform::from_xyz(x, y, TELEGRAPH_Z),
+        Telegraph {
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+            kind,
+            damage,
+        },
+        RunEntity,
+    ));
+}
+
+/// Replace telegraphs whose warning time is up with the real obstacle
+fn resolve_telegraphs(
+    mut commands: Commands,
+    mut telegraphs: Query<(Entity, &mut Telegraph, &Transform)>,
+    time: Res<Time>,
+) {
+    for (entity, mut telegraph, transform) in &mut telegraphs {
+        if !telegraph.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let color = if telegraph.damage.is_heavy() {
+            HEAVY_OBSTACLE_COLOR
+        } else {
+            OBSTACLE_COLOR
+        };
+
+        commands.entity(entity).despawn();
+        commands.spawn((
+            Sprite::from_color(color, telegraph.kind.sprite_size()),
+            transform.with_translation(transform.translation.with_z(OBSTACLE_Z)),
+            Obstacle,
+            telegraph.kind,
+            telegraph.kind.hitbox(),
+            telegraph.damage,
+            Collider,
+            RunEntity,
+        ));
+    }
+}
+
+fn blink_telegraphs(mut telegraphs: Query<(&Telegraph, &mut Visibility)>) {
+    for (telegraph, mut visibility) in &mut telegraphs {
+        let shown =
+            ((telegraph.timer.elapsed_secs() * TELEGRAPH_BLINK_RATE) as u32).is_multiple_of(2);
+        *visibility = if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn spawn_walls(
+    mut commands: Commands,
+    mut spawner: ResMut<WallSpawner>,
+    difficulty: Res<Difficulty>,
+    mut rng: ResMut<GameRng>,
+    camera_transform: Single<&Transform, With<MainCamera>>,
+    safe_lanes: Query<&SafeLane>,
+    time: Res<Time>,
+) {
+    if !spawner.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let x = camera_transform.translation.x + WALL_SPAWN_AHEAD;
+    if safe_lanes.iter().any(|lane| lane.contains(x)) {
+        return;
+    }
+    let gap = (WALL_GAP / **difficulty).max(MIN_WALL_GAP);
+    let phase = rng.random::<f32>() * std::f32::consts::TAU;
+    let offset = (gap + WALL_SEGMENT_HEIGHT) / 2.0;
+
+    // Both halves share a phase so the gap between them keeps its size
+    for base_y in [offset, -offset] {
+        commands.spawn((
+            Sprite::from_color(WALL_COLOR, Vec2::new(WALL_WIDTH, WALL_SEGMENT_HEIGHT)),
+            Transform::from_xyz(x, base_y, OBSTACLE_Z),
+            MovingWall { base_y, phase },
+            Obstacle,
+            Hitbox(Vec2::new(WALL_WIDTH, WALL_SEGMENT_HEIGHT)),
+            Damage(OBSTACLE_DAMAGE),
+            Collider,
+            RunEntity,
+        ));
+    }
+}
+
+fn move_walls(mut walls: Query<(&mut Transform, &MovingWall)>, time: Res<Time>) {
+    for (mut transform, wall) in &mut walls {
+        let swing = (time.elapsed_secs() * WALL_SPEED + wall.phase).sin();
+        transform.translation.y = wall.base_y + swing * WALL_AMPLITUDE;
+    }
+}
+
+/// Hit the player for `damage` hit points and make them briefly
+/// invulnerable. Fractions of a hit point add up over several hits, and
+/// health never drops below 0.
+pub fn hurt_player(commands: &mut Commands, player: Entity, health: &mut Health, damage: f32) {
+    let before = health.current;
+    health.partial += damage;
+    let whole = health.partial.floor();
+    health.partial -= whole;
+    health.current = (health.current - whole as i32).max(0);
+    debug!(damage, health = health.current, "player hurt");
+    commands.entity(player).insert(Invulnerable::after_hit());
+    commands.send_event(PlayerHurt);
+    commands.send_event(HealthChanged {
+        current: health.current,
+        max: health.max,
+    });
+
+    if before > 1 && health.current == 1 {
+        flash::spawn_critical_flash(commands);
+    }
+}
+
+fn tick_invulnerability(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &mut Invulnerable)>,
+    time: Res<Time>,
+) {
+    for (entity, mut invulnerable) in &mut player_query {
+        if invulnerable.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn obstacle_collisions(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &Transform, &mut Health, Has<Invulnerable>), With<Player>>,
+    obstacle_query: Query<(Entity, &Transform, &Hitbox, &Damage, Has<MovingWall>), With<Obstacle>>,
+    settings: Res<GameSettings>,
+) {
+    let mut removed = Vec::new();
+
+    for (player_entity, player_transform, mut health, invulnerable) in &mut player_query {
+        if invulnerable {
+            continue;
+        }
+        let player_pos = player_transform.translation.truncate();
+
+        for (obstacle_entity, transform, hitbox, damage, is_wall) in &obstacle_query {
+            if removed.contains(&obstacle_entity) {
+                continue;
+            }
+            let obstacle_pos = transform.translation.truncate();
+            if !aabb_overlap(player_pos, PLAYER_HITBOX, obstacle_pos, **hitbox) {
+                continue;
+            }
+
+            // Walls stay where they are, anything else is removed so a single
+            // hit only counts once
+            if !is_wall {
+                commands.entity(obstacle_entity).despawn();
+                removed.push(obstacle_entity);
+            }
+
+            hurt_player(
+                &mut commands,
+                player_entity,
+                &mut health,
+                **damage as f32 * settings.assist_mode.damage_multiplier(),
+            );
+            // at most one hit per player each tick
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{ecs::system::RunSystemOnce, state::app::StatesPlugin, time::TimeUpdateStrategy};
+
+    use super::*;
+    use crate::{pause_game_clock, spawn_level, MAX_HEALTH};
+
+    #[test]
+    fn heavy_obstacle_takes_two_health() {
+        let mut world = World::new();
+        world.init_resource::<Events<PlayerHurt>>();
+        world.init_resource::<Events<HealthChanged>>();
+        world.insert_resource(GameSettings::default());
+        let player = world
+            .spawn((
+                Player,
+                Transform::default(),
+                Health {
+                    current: MAX_HEALTH,
+                    max: MAX_HEALTH,
+                    partial: 0.0,
+                },
+            ))
+            .id();
+        world.spawn((
+            Obstacle,
+            Transform::default(),
+            Hitbox(Vec2::splat(50.0)),
+            Damage(HEAVY_OBSTACLE_DAMAGE),
+        ));
+
+        world.run_system_once(obstacle_collisions).unwrap();
+
+        let health = world.get::<Health>(player).unwrap();
+        assert_eq!(health.current, MAX_HEALTH - HEAVY_OBSTACLE_DAMAGE);
+        assert!(world.get::<Invulnerable>(player).is_some());
+    }
+
+    #[test]
+    fn rugs_survive_a_hit_just_after_spawning() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .add_event::<PlayerHurt>()
+            .add_event::<HealthChanged>()
+            .insert_resource(GameSettings::default())
+            .add_systems(Startup, spawn_level)
+            .add_systems(Update, obstacle_collisions);
+        app.update();
+
+        let world = app.world_mut();
+        let (player, transform) = world
+            .query_filtered::<(Entity, &Transform), With<Player>>()
+            .single(world);
+        world.spawn((
+            Obstacle,
+            *transform,
+            Hitbox(Vec2::splat(50.0)),
+            Damage(OBSTACLE_DAMAGE),
+        ));
+        app.update();
+
+        let health = app.world().get::<Health>(player).unwrap();
+        assert_eq!(health.current, MAX_HEALTH);
+    }
+
+    #[test]
+    fn invulnerability_holds_while_paused() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .insert_state(GameState::Playing)
+            .add_systems(OnEnter(GameState::Paused), pause_game_clock)
+            .add_systems(FixedUpdate, tick_invulnerability);
+        let player = app.world_mut().spawn(Invulnerable::new(10.0)).id();
+        app.update();
+
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Paused);
+        app.update();
+        let elapsed = |app: &App| app.world().get::<Invulnerable>(player).unwrap().elapsed();
+        let paused_at = elapsed(&app);
+        for _ in 0..5 {
+            app.update();
+        }
+
+        assert_eq!(elapsed(&app), paused_at);
+    }
+
+    #[test]
+    fn spawners_start_over_for_the_next_run() {
+        let mut world = World::new();
+        world.insert_resource(SpawnTable::default());
+        reset_spawners(&mut world);
+        world
+            .resource_mut::<ObstacleSpawner>()
+            .0
+            .tick(Duration::from_secs_f32(1.5));
+        world
+            .resource_mut::<WallSpawner>()
+            .0
+            .tick(Duration::from_secs_f32(5.0));
+        world.resource_mut::<SafeLaneSpawner>().0 = Timer::from_seconds(0.5, TimerMode::Once);
+
+        reset_spawners(&mut world);
+
+        let table = SpawnTable::default();
+        assert_eq!(world.resource::<ObstacleSpawner>().0.elapsed_secs(), 0.0);
+        assert_eq!(world.resource::<WallSpawner>().0.elapsed_secs(), 0.0);
+        assert_eq!(
+            world
+                .resource::<SafeLaneSpawner>()
+                .0
+                .duration()
+                .as_secs_f32(),
+            table.safe_lane_interval
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+
+use crate::{combo::Combo, CollisionEvent, GameState};
+
+const TRAIL_COLOR: Color = Color::srgb(1.0, 0.85, 0.4);
+/// Seconds a link between two pickups takes to fade away
+const TRAIL_FADE: f32 = 0.4;
+
+/// Plugin for the light streaks linking gems collected in a combo
+pub struct PickupTrailPlugin;
+
+impl Plugin for PickupTrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PickupTrail>().add_systems(
+            Update,
+            (extend_pickup_trail, draw_pickup_trail)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// Where the last gem of the running combo was collected, and the fading
+/// links drawn between pickups
+#[derive(Resource, Default)]
+pub struct PickupTrail {
+    last: Option<Vec2>,
+    links: Vec<TrailLink>,
+}
+
+struct TrailLink {
+    from: Vec2,
+    to: Vec2,
+    timer: Timer,
+}
+
+fn extend_pickup_trail(
+    mut collisions: EventReader<CollisionEvent>,
+    mut trail: ResMut<PickupTrail>,
+    combo: Res<Combo>,
+) {
+    // the combo ran out, so the next pickup starts a new trail
+    if combo.window.finished() {
+        trail.last = None;
+    }
+
+    for collision in collisions.read() {
+        if let Some(from) = trail.last {
+            trail.links.push(TrailLink {
+                from,
+                to: collision.position,
+                timer: Timer::from_seconds(TRAIL_FADE, TimerMode::Once),
+            });
+        }
+        trail.last = Some(collision.position);
+    }
+}
+
+fn draw_pickup_trail(mut gizmos: Gizmos, mut trail: ResMut<PickupTrail>, time: Res<Time>) {
+    trail
+        .links
+        .retain_mut(|link| !link.timer.tick(time.delta()).finished());
+
+    for link in &trail.links {
+        let color = TRAIL_COLOR.with_alpha(link.timer.fraction_remaining());
+        gizmos.line_2d(link.from, link.to, color);
+    }
+}
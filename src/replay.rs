@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    menu::MainMenuUi, navigation::navigate_menus, save::SaveData, GameState, GameplaySet, HudText,
+    MainCamera, Player, PlayerId, PLAYER_SIZE, PLAYER_Z, TEXT_COLOR,
+};
+
+/// Seconds between two recorded positions; playback interpolates between them
+const RECORD_INTERVAL: f32 = 0.125;
+/// Longest recording kept, one minute of play
+const MAX_REPLAY_POINTS: usize = 480;
+/// Recordings shorter than this aren't worth showing off
+const MIN_REPLAY_POINTS: usize = 40;
+
+/// Idle time on the main menu before the demo starts
+const ATTRACT_DELAY: f32 = 20.0;
+/// How far ahead of the demo rug the camera looks, like during play
+const DEMO_CAMERA_LEAD: f32 = 200.0;
+
+/// Plugin to record the player's path and play it back as an attract mode
+/// on the main menu
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recording>()
+            .insert_resource(AttractTimer(Timer::from_seconds(
+                ATTRACT_DELAY,
+                TimerMode::Once,
+            )))
+            .add_systems(OnEnter(GameState::MainMenu), clear_recording)
+            .add_systems(OnExit(GameState::GameOver), clear_recording)
+            .add_systems(OnEnter(GameState::GameOver), keep_recording)
+            .add_systems(OnExit(GameState::MainMenu), reset_attract_timer)
+            .add_systems(FixedUpdate, record_player.in_set(GameplaySet::Collision))
+            .add_systems(
+                Update,
+                (start_demo, play_demo, end_demo_on_input)
+                    .chain()
+                    .before(navigate_menus)
+                    .run_if(in_state(GameState::MainMenu)),
+            );
+    }
+}
+
+/// The player's path through a run, sampled every `RECORD_INTERVAL`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Replay {
+    points: Vec<[f32; 2]>,
+}
+
+impl Replay {
+    /// Position `elapsed` seconds into the replay, or `None` once it is over
+    fn position_at(&self, elapsed: f32) -> Option<Vec2> {
+        let index = (elapsed / RECORD_INTERVAL) as usize;
+        let from = Vec2::from(*self.points.get(index)?);
+        let to = Vec2::from(*self.points.get(index + 1)?);
+        Some(from.lerp(to, (elapsed / RECORD_INTERVAL).fract()))
+    }
+}
+
+/// Replay being recorded for the current run
+#[derive(Resource, Default)]
+struct Recording {
+    replay: Replay,
+    since_last: f32,
+}
+
+/// Time left on the main menu without any input before the demo starts
+#[derive(Resource, Deref, DerefMut)]
+struct AttractTimer(Timer);
+
+/// The rug flying the demo, and how far into the replay it is
+#[derive(Component)]
+struct DemoPlayback {
+    elapsed: f32,
+}
+
+#[derive(Component)]
+struct DemoUi;
+
+fn clear_recording(mut recording: ResMut<Recording>) {
+    *recording = Recording::default();
+}
+
+fn record_player(
+    mut recording: ResMut<Recording>,
+    players: Query<(&Transform, &PlayerId), With<Player>>,
+    time: Res<Time>,
+) {
+    // the demo only shows the first player's rug
+    let Some((player, _)) = players.iter().find(|(_, id)| id.0 == 0) else {
+        return;
+    };
+    if recording.replay.points.len() >= MAX_REPLAY_POINTS {
+        return;
+    }
+
+    recording.since_last += time.delta_secs();
+    if recording.since_last < RECORD_INTERVAL && !recording.replay.points.is_empty() {
+        return;
+    }
+
+    recording.since_last = 0.0;
+    recording
+        .replay
+        .points
+        .push(player.translation.truncate().to_array());
+}
+
+/// Keep the finished run as the demo for the attract mode
+fn keep_recording(recording: Res<Recording>, mut save: ResMut<SaveData>) {
+    if recording.replay.points.len() < MIN_REPLAY_POINTS {
+        return;
+    }
+
+    save.demo = recording.replay.clone();
+    save.save();
+}
+
+fn start_demo(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut attract_timer: ResMut<AttractTimer>,
+    save: Res<SaveData>,
+    demo: Query<(), With<DemoPlayback>>,
+    mut menu: Query<&mut Visibility, With<MainMenuUi>>,
+    time: Res<Time>,
+) {
+    if !demo.is_empty()
+        || !attract_timer.tick(time.delta()).just_finished()
+        || save.demo.points.len() < MIN_REPLAY_POINTS
+    {
+        return;
+    }
+
+    for mut visibility in &mut menu {
+        *visibility = Visibility::Hidden;
+    }
+
+    commands.spawn((
+        Sprite {
+            image: asset_server.load("sprites/rug.png"),
+            custom_size: Some(Vec2::splat(PLAYER_SIZE)),
+            ..default()
+        },
+        Transform::from_translation(Vec2::from(save.demo.points[0]).extend(PLAYER_Z)),
+        DemoPlayback { elapsed: 0.0 },
+        StateScoped(GameState::MainMenu),
+    ));
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::new("DEMO - press any key"),
+        TextFont::default(),
+        HudText(1.0),
+        TextColor(TEXT_COLOR),
+        DemoUi,
+        StateScoped(GameState::MainMenu),
+    ));
+}
+
+/// Fly the demo rug along the recorded path with the camera following it
+fn play_demo(
+    mut demo: Query<(&mut DemoPlayback, &mut Transform)>,
+    mut camera_transform: Single<&mut Transform, (With<MainCamera>, Without<DemoPlayback>)>,
+    save: Res<SaveData>,
+    mut next_state: ResMut<NextState<GameState>>,
+    time: Res<Time>,
+) {
+    let Ok((mut playback, mut transform)) = demo.get_single_mut() else {
+        return;
+    };
+
+    playback.elapsed += time.delta_secs();
+    let Some(position) = save.demo.position_at(playback.elapsed) else {
+        // re-entering the menu clears the demo away and resets the camera
+        next_state.set(GameState::MainMenu);
+        return;
+    };
+
+    transform.translation = position.extend(PLAYER_Z);
+    camera_transform.translation.x = position.x + DEMO_CAMERA_LEAD;
+}
+
+/// Any key or click returns to the menu, and is swallowed so it doesn't
+/// also pick a menu entry
+fn end_demo_on_input(
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut mouse_input: ResMut<ButtonInput<MouseButton>>,
+    mut gamepads: Query<&mut Gamepad>,
+    mut attract_timer: ResMut<AttractTimer>,
+    demo: Query<(), With<DemoPlayback>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let any_input = keyboard_input.get_just_pressed().next().is_some()
+        || mouse_input.get_just_pressed().next().is_some()
+        || gamepads
+            .iter()
+            .any(|gamepad| gamepad.get_just_pressed().next().is_some());
+    if !any_input {
+        return;
+    }
+
+    attract_timer.reset();
+    if demo.is_empty() {
+        return;
+    }
+
+    keyboard_input.clear();
+    mouse_input.clear();
+    for mut gamepad in &mut gamepads {
+        gamepad.digital_mut().clear();
+    }
+    next_state.set(GameState::MainMenu);
+}
+
+fn reset_attract_timer(mut attract_timer: ResMut<AttractTimer>) {
+    attract_timer.reset();
+}
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{collect_gems, GameState, GameplaySet, Health, HealthChanged, Player, PlayerId};
+
+/// Seconds of history kept, and so how far back a rewind goes
+const REWIND_SECONDS: f32 = 3.0;
+/// Snapshots kept at the default fixed timestep of 64 Hz
+const MAX_SNAPSHOTS: usize = (REWIND_SECONDS * 64.0) as usize;
+
+const REWIND_GEM_COLOR: Color = Color::srgb(0.7, 0.5, 1.0);
+
+/// Plugin for the rare gems that wind the players back a few seconds
+pub struct RewindPlugin;
+
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StateHistory>()
+            .add_event::<RewindCollected>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    rewind.after(collect_gems).in_set(GameplaySet::Collision),
+                    // recorded after collisions, so each snapshot has the
+                    // damage taken that tick
+                    record_history
+                        .after(GameplaySet::Collision)
+                        .run_if(in_state(GameState::Playing)),
+                ),
+            )
+            .add_systems(Update, tint_rewind_gems);
+    }
+}
+
+/// A gem that restores every player's position and health from a few
+/// seconds ago when collected
+#[derive(Component)]
+pub struct RewindGem;
+
+/// A rewind gem was collected
+#[derive(Event)]
+pub struct RewindCollected;
+
+/// Where a player was and how healthy, at one fixed tick
+struct Snapshot {
+    player: PlayerId,
+    translation: Vec3,
+    health: i32,
+}
+
+/// Rolling record of the players' state over the last `REWIND_SECONDS`,
+/// oldest first
+#[derive(Resource, Default)]
+pub struct StateHistory(VecDeque<Vec<Snapshot>>);
+
+fn record_history(
+    players: Query<(&PlayerId, &Transform, &Health), With<Player>>,
+    mut history: ResMut<StateHistory>,
+) {
+    if history.0.len() == MAX_SNAPSHOTS {
+        history.0.pop_front();
+    }
+    history.0.push_back(
+        players
+            .iter()
+            .map(|(player, transform, health)| Snapshot {
+                player: *player,
+                translation: transform.translation,
+                health: health.current,
+            })
+            .collect(),
+    );
+}
+
+/// Move the players back to the oldest snapshot. Health is only ever given
+/// back, never taken away, and the history starts over so the same moment
+/// can't be rewound to twice.
+fn rewind(
+    mut commands: Commands,
+    mut events: EventReader<RewindCollected>,
+    mut players: Query<(&PlayerId, &mut Transform, &mut Health), With<Player>>,
+    mut history: ResMut<StateHistory>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let Some(oldest) = history.0.pop_front() else {
+        return;
+    };
+    history.0.clear();
+
+    for (player, mut transform, mut health) in &mut players {
+        let Some(snapshot) = oldest.iter().find(|snapshot| snapshot.player == *player) else {
+            continue;
+        };
+
+        transform.translation = snapshot.translation;
+        if snapshot.health > health.current {
+            health.current = snapshot.health.min(health.max);
+            commands.send_event(HealthChanged {
+                current: health.current,
+                max: health.max,
+            });
+        }
+    }
+}
+
+fn tint_rewind_gems(mut gems: Query<&mut Sprite, Added<RewindGem>>) {
+    for mut sprite in &mut gems {
+        sprite.color = REWIND_GEM_COLOR;
+    }
+}
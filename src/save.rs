@@ -0,0 +1,389 @@
+use std::{cmp::Reverse, collections::BTreeMap, fs};
+
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize, Deserializer, Serialize,
+};
+
+use crate::replay::Replay;
+
+/// File the save data is written to, relative to the working directory
+const SAVE_PATH: &str = "magic_rug_save.ron";
+
+/// How many scores the local leaderboard keeps
+const LEADERBOARD_SIZE: usize = 10;
+/// Scores above this can't come from a real run, so are treated as edited
+const MAX_PLAUSIBLE_SCORE: usize = 100_000_000;
+/// Mixed into leaderboard checksums, so an edited score can't just be given
+/// a freshly computed plain hash
+const CHECKSUM_KEY: &[u8] = b"magic rug leaderboard";
+
+/// Plugin to load the save file on startup
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SaveData::load());
+    }
+}
+
+/// Version files written before versioning are read as
+const UNVERSIONED: u32 = 1;
+
+/// Progress that is kept between sessions
+#[derive(Resource, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct SaveData {
+    #[serde(default = "unversioned")]
+    pub version: u32,
+    /// Best daily challenge score, keyed by the challenge's date
+    pub daily_best: BTreeMap<String, usize>,
+    /// Seed of the most recently finished run
+    pub last_seed: Option<u64>,
+    /// Best scores, highest first
+    #[serde(deserialize_with = "lenient_leaderboard")]
+    pub leaderboard: Vec<LeaderboardEntry>,
+    /// Path of a recent run, played back by the attract mode
+    pub demo: Replay,
+    /// Most gems collected in a row without getting hit
+    pub best_no_hit_streak: usize,
+    /// Fastest finished time attack, in seconds
+    pub best_time_attack: Option<f32>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct LeaderboardEntry {
+    pub score: usize,
+    /// Day the score was set (YYYY-MM-DD)
+    pub date: String,
+    /// `LeaderboardEntry::expected_checksum` when the entry was written.
+    /// Entries from before checksums were added read as 0, so they can't be
+    /// told apart from edited ones and are dropped on load.
+    checksum: u64,
+}
+
+/// Leaderboard entry as it was read from the file, with loose types so an
+/// entry with a missing field or a negative score can be dropped on its own
+#[derive(Deserialize)]
+struct RawLeaderboardEntry {
+    score: Option<i64>,
+    date: Option<String>,
+    checksum: Option<u64>,
+}
+
+/// Element of the leaderboard list, which might not be an entry at all
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MaybeLeaderboardEntry {
+    Entry(RawLeaderboardEntry),
+    Unreadable(IgnoredAny),
+}
+
+impl TryFrom<RawLeaderboardEntry> for LeaderboardEntry {
+    type Error = ();
+
+    fn try_from(raw: RawLeaderboardEntry) -> Result<Self, ()> {
+        let score = raw.score.and_then(|score| usize::try_from(score).ok());
+        let (Some(score), Some(date)) = (score, raw.date) else {
+            return Err(());
+        };
+        Ok(LeaderboardEntry {
+            score,
+            date,
+            checksum: raw.checksum.unwrap_or(0),
+        })
+    }
+}
+
+/// Read the leaderboard one entry at a time, dropping the ones that can't be
+/// read rather than failing the whole save file
+fn lenient_leaderboard<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<LeaderboardEntry>, D::Error> {
+    let read = Vec::<MaybeLeaderboardEntry>::deserialize(deserializer)?;
+    let total = read.len();
+    let entries: Vec<LeaderboardEntry> = read
+        .into_iter()
+        .filter_map(|entry| match entry {
+            MaybeLeaderboardEntry::Entry(raw) => raw.try_into().ok(),
+            MaybeLeaderboardEntry::Unreadable(_) => None,
+        })
+        .collect();
+
+    let dropped = total - entries.len();
+    if dropped > 0 {
+        warn!("dropped {dropped} unreadable leaderboard entries from {SAVE_PATH}");
+    }
+    Ok(entries)
+}
+
+impl LeaderboardEntry {
+    pub fn new(score: usize, date: String) -> Self {
+        let mut entry = LeaderboardEntry {
+            score,
+            date,
+            checksum: 0,
+        };
+        entry.checksum = entry.expected_checksum();
+        entry
+    }
+
+    /// FNV-1a hash of the key, score and date. Not secure, but enough to
+    /// catch a hand-edited or corrupted file.
+    fn expected_checksum(&self) -> u64 {
+        let score = (self.score as u64).to_le_bytes();
+        let bytes = CHECKSUM_KEY
+            .iter()
+            .chain(&score)
+            .chain(self.date.as_bytes());
+        bytes.fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Whether the entry could have been set by a real run and wasn't
+    /// changed since
+    fn is_plausible(&self) -> bool {
+        self.score <= MAX_PLAUSIBLE_SCORE
+            && self.date.len() == "YYYY-MM-DD".len()
+            && self.checksum == self.expected_checksum()
+    }
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        SaveData {
+            version: SaveData::VERSION,
+            daily_best: BTreeMap::new(),
+            last_seed: None,
+            leaderboard: Vec::new(),
+            demo: Replay::default(),
+            best_no_hit_streak: 0,
+            best_time_attack: None,
+        }
+    }
+}
+
+impl Versioned for SaveData {
+    const VERSION: u32 = 3;
+
+    fn version_mut(&mut self) -> &mut u32 {
+        &mut self.version
+    }
+}
+
+impl SaveData {
+    pub fn load() -> Self {
+        let mut save: SaveData = load_versioned(SAVE_PATH);
+        save.discard_implausible_scores();
+        save
+    }
+
+    /// Drop leaderboard entries that fail validation, and put the rest back
+    /// in order in case the file was reordered
+    fn discard_implausible_scores(&mut self) {
+        let before = self.leaderboard.len();
+        self.leaderboard.retain(LeaderboardEntry::is_plausible);
+        let dropped = before - self.leaderboard.len();
+        if dropped > 0 {
+            warn!("dropped {dropped} invalid leaderboard entries from {SAVE_PATH}");
+        }
+
+        self.leaderboard.sort_by_key(|entry| Reverse(entry.score));
+        self.leaderboard.truncate(LEADERBOARD_SIZE);
+    }
+
+    pub fn save(&self) {
+        save_ron(SAVE_PATH, self);
+    }
+
+    /// Add a score to the leaderboard, keeping it sorted and at most
+    /// `LEADERBOARD_SIZE` long. Ties rank below the scores already there.
+    ///
+    /// Returns the entry's rank, or `None` if it didn't make the leaderboard.
+    pub fn insert_score(&mut self, entry: LeaderboardEntry) -> Option<usize> {
+        let rank = self
+            .leaderboard
+            .iter()
+            .position(|existing| existing.score < entry.score)
+            .unwrap_or(self.leaderboard.len());
+        if rank >= LEADERBOARD_SIZE {
+            return None;
+        }
+
+        self.leaderboard.insert(rank, entry);
+        self.leaderboard.truncate(LEADERBOARD_SIZE);
+        Some(rank)
+    }
+}
+
+/// A file whose layout changes as the game evolves. The version is written
+/// into the file so older files can be upgraded when they are loaded.
+pub trait Versioned: Serialize + DeserializeOwned + Default {
+    /// Version of the layout this build writes
+    const VERSION: u32;
+
+    fn version_mut(&mut self) -> &mut u32;
+
+    /// Upgrade data read from a file at version `from` to the current layout.
+    ///
+    /// Fields added since are already filled in by `serde(default)`, so this
+    /// only needs overriding for changes that aren't simple additions.
+    fn migrate(&mut self, from: u32) {
+        let _ = from;
+    }
+}
+
+/// Field default for files written before they carried a version
+pub fn unversioned() -> u32 {
+    UNVERSIONED
+}
+
+/// Read a versioned RON file, upgrading it from older versions.
+///
+/// Files that can't be parsed or come from a newer version of the game are
+/// copied to a `.bak` file next to them, so the data isn't lost once the
+/// defaults get saved over them.
+pub fn load_versioned<T: Versioned>(path: &str) -> T {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return T::default();
+    };
+
+    parse_versioned(path, &contents).unwrap_or_else(|| {
+        back_up(path);
+        T::default()
+    })
+}
+
+/// Parse the contents of the versioned RON file at `path`, upgrading them
+/// from older versions. `None` if they can't be parsed or come from a newer
+/// version of the game.
+fn parse_versioned<T: Versioned>(path: &str, contents: &str) -> Option<T> {
+    let mut value: T = match ron::from_str(contents) {
+        Ok(value) => value,
+        Err(err) => {
+            warn!("ignoring unreadable file {path}: {err}");
+            return None;
+        }
+    };
+
+    let version = *value.version_mut();
+    if version > T::VERSION {
+        warn!(
+            "ignoring {path}, written by a newer version ({version}, expected {})",
+            T::VERSION
+        );
+        return None;
+    }
+    if version < T::VERSION {
+        info!("upgrading {path} from version {version} to {}", T::VERSION);
+        value.migrate(version);
+        *value.version_mut() = T::VERSION;
+    }
+    Some(value)
+}
+
+fn back_up(path: &str) {
+    let backup = format!("{path}.bak");
+    if let Err(err) = fs::copy(path, &backup) {
+        warn!("failed to back up {path} to {backup}: {err}");
+    }
+}
+
+/// Read a RON file, falling back to the default value if it is missing or
+/// can't be parsed
+pub fn load_ron<T: DeserializeOwned + Default>(path: &str) -> T {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return T::default();
+    };
+
+    ron::from_str(&contents).unwrap_or_else(|err| {
+        warn!("ignoring unreadable file {path}: {err}");
+        T::default()
+    })
+}
+
+/// Write a RON file, logging instead of failing if that isn't possible
+pub fn save_ron<T: Serialize>(path: &str, value: &T) {
+    let contents = match ron::ser::to_string_pretty(value, PrettyConfig::default()) {
+        Ok(contents) => contents,
+        Err(err) => {
+            warn!("failed to serialize {path}: {err}");
+            return;
+        }
+    };
+
+    if let Err(err) = fs::write(path, contents) {
+        warn!("failed to write {path}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Save file as written before versioning and checksums
+    const V1_SAVE: &str = r#"(
+        daily_best: {"2026-10-01": 420},
+        last_seed: Some(7),
+        leaderboard: [(score: 1200, date: "2026-10-01")],
+        best_no_hit_streak: 12,
+    )"#;
+
+    #[test]
+    fn upgrades_an_unversioned_save() {
+        let save: SaveData = parse_versioned("save.ron", V1_SAVE).unwrap();
+
+        assert_eq!(save.version, SaveData::VERSION);
+        assert_eq!(save.daily_best.get("2026-10-01"), Some(&420));
+        assert_eq!(save.last_seed, Some(7));
+        assert_eq!(save.best_no_hit_streak, 12);
+        // scores from before checksums can't be vouched for
+        assert_eq!(save.leaderboard.len(), 1);
+        assert!(!save.leaderboard[0].is_plausible());
+    }
+
+    #[test]
+    fn drops_bad_leaderboard_entries_one_at_a_time() {
+        let contents = format!(
+            r#"(
+                version: 2,
+                leaderboard: [
+                    (score: 900, date: "2026-10-02", checksum: {}),
+                    (score: 850, date: "2026-10-02"),
+                    (score: -5, date: "2026-10-02"),
+                    (date: "2026-10-02"),
+                    (score: "lots", date: "2026-10-02"),
+                    (score: 800, date: "2026-10-02", checksum: 5),
+                    (score: {}, date: "2026-10-02"),
+                ],
+                best_no_hit_streak: 3,
+            )"#,
+            LeaderboardEntry::new(900, "2026-10-02".to_string()).checksum,
+            MAX_PLAUSIBLE_SCORE + 1
+        );
+
+        let mut save: SaveData = parse_versioned("save.ron", &contents).unwrap();
+        // the unreadable ones are gone already, the rest are checked on load
+        assert_eq!(save.leaderboard.len(), 4);
+        save.discard_implausible_scores();
+
+        assert_eq!(save.best_no_hit_streak, 3);
+        assert_eq!(save.leaderboard.len(), 1);
+        assert_eq!(save.leaderboard[0].score, 900);
+    }
+
+    #[test]
+    fn reads_back_a_saved_leaderboard() {
+        let mut save = SaveData::default();
+        save.insert_score(LeaderboardEntry::new(1500, "2026-10-03".to_string()));
+        let contents = ron::to_string(&save).unwrap();
+
+        let mut loaded: SaveData = parse_versioned("save.ron", &contents).unwrap();
+        loaded.discard_implausible_scores();
+        assert_eq!(loaded.leaderboard.len(), 1);
+        assert_eq!(loaded.leaderboard[0].score, 1500);
+    }
+}
@@ -0,0 +1,619 @@
+use std::{collections::BTreeMap, mem};
+
+use bevy::{
+    audio::Volume, ecs::system::SystemParam, prelude::*, window::PresentMode as WindowPresentMode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::save::{load_versioned, save_ron, unversioned, Versioned};
+
+/// File the settings are written to, relative to the working directory
+const SETTINGS_PATH: &str = "magic_rug_settings.ron";
+
+/// Plugin to load the player's settings on startup
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = GameSettings::load();
+        app.insert_resource(settings.difficulty_curve.clone())
+            .insert_resource(MasterVolume(settings.master_volume))
+            .insert_resource(MusicVolume(settings.music_volume))
+            .insert_resource(SfxVolume(settings.sfx_volume))
+            .insert_resource(settings)
+            .add_systems(PostUpdate, save_volumes);
+    }
+}
+
+/// Player preferences, persisted between sessions
+#[derive(Resource, Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GameSettings {
+    #[serde(default = "unversioned")]
+    pub version: u32,
+    /// Show the panel listing what each gem kind is worth
+    pub show_gem_legend: bool,
+    /// What is shown of the rugs' health above the health bar
+    pub health_display: HealthDisplayMode,
+    /// Read from files before version 3, replaced by `health_display`
+    #[serde(skip_serializing)]
+    show_health_number: Option<bool>,
+    /// How scores are written on the scoreboard and the leaderboard
+    pub score_format: ScoreFormat,
+    /// Orthographic scale of the game camera; above 1 shows more of the level
+    pub camera_zoom: f32,
+    /// Keep the playfield at this width / height ratio, filling the rest of
+    /// the window with black bars. `None` uses the whole window.
+    pub letterbox_aspect: Option<f32>,
+    /// Base size of the HUD text, before `ui_scale`
+    pub hud_font_size: f32,
+    /// Scale applied to the whole UI, for high-DPI screens or readability
+    pub ui_scale: f32,
+    /// Play `music_playlist` in the background. Off by default since no
+    /// tracks are shipped with the game.
+    pub music: bool,
+    /// Music tracks, relative to the assets folder. The playlist starts over
+    /// after the last one.
+    pub music_playlist: Vec<String>,
+    /// Beats per minute of playlist tracks, by path, for spawning gems on
+    /// the beat. Tracks left out are taken to be 120.
+    pub track_bpm: BTreeMap<String, f32>,
+    pub playlist_order: PlaylistOrder,
+    /// Track looped on the menus, crossfading with the playlist when a run
+    /// starts or ends. `None` keeps the playlist going everywhere.
+    pub menu_music: Option<String>,
+    pub assist_mode: AssistMode,
+    /// Analog stick travel, from 0 to 1, that is ignored to hide drift
+    pub deadzone: f32,
+    /// Swap up and down for the keys and sticks
+    pub invert_vertical: bool,
+    /// Key each player holds to slow the scroll down, the second one only
+    /// used in co-op
+    pub brake_keys: [KeyCode; 2],
+    /// Fraction of the scroll speed the rug slows to while braking, from 0
+    /// to 1
+    pub brake_min_speed: f32,
+    /// Volumes from 0 to 1, as last set. While the game runs they are kept
+    /// in `MasterVolume`, `MusicVolume` and `SfxVolume`.
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    /// Play pickup sounds from the side of the screen the gem was on
+    pub pan_pickup_sounds: bool,
+    /// How far ahead of the rug the camera looks at the normal scroll speed
+    pub camera_lead_x: f32,
+    /// Nudge the camera towards obstacles coming up ahead
+    pub camera_danger_nudge: bool,
+    /// Keep the camera from showing past the ends of a level of set length,
+    /// `None` for an endless level
+    pub camera_bounds: Option<CameraBounds>,
+    /// Where the rug starts a run
+    pub player_start: (f32, f32),
+    /// Play a sound and shake the camera when a combo runs out
+    pub combo_break_feedback: bool,
+    /// Combo counts that give every rug a hit point back when reached
+    pub combo_heal_milestones: Vec<u32>,
+    /// Combo that revives the rugs once per run if they run out of health
+    /// while holding it, `None` to never revive
+    pub second_wind_combo: Option<u32>,
+    pub difficulty_curve: DifficultyCurve,
+    /// What else ends a run, besides every rug running out of health
+    pub death_condition: DeathCondition,
+    /// Fuel the rug burns per second, out of a full tank of 100
+    pub fuel_drain_rate: f32,
+    /// Fuel each collected gem puts back
+    pub fuel_per_gem: f32,
+    /// Add a second rug, steered with W/S and dashing with E
+    pub coop: bool,
+    pub graphics_quality: GraphicsQuality,
+    /// How gems are spread over the height of the level
+    pub gem_y_distribution: YDistribution,
+    /// Drop gems in bursts on the beat of the music, rather than streaming
+    /// them in evenly
+    pub beat_spawning: bool,
+    /// How far past the left edge of the view gems and obstacles are kept
+    /// before being removed. Generous, so a gem the camera shakes or zooms
+    /// back onto is still there.
+    pub despawn_margin: f32,
+    /// Closest two gems are spawned to each other, center to center
+    pub gem_separation: f32,
+    /// Streamed gems drift up and down, bouncing off the top and bottom of
+    /// the level
+    pub drifting_gems: bool,
+    pub present_mode: PresentMode,
+    /// Most frames drawn per second, `None` for no limit beyond the present
+    /// mode's
+    pub frame_cap: Option<f32>,
+    /// Turn the lighting from day to dusk to night over a run
+    pub time_of_day: bool,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            version: GameSettings::VERSION,
+            show_gem_legend: true,
+            health_display: HealthDisplayMode::Numeric,
+            show_health_number: None,
+            score_format: ScoreFormat::Separated,
+            camera_zoom: 1.0,
+            letterbox_aspect: None,
+            hud_font_size: 33.0,
+            ui_scale: 1.0,
+            music: false,
+            music_playlist: vec!["sounds/music.ogg".to_string()],
+            track_bpm: BTreeMap::from([("sounds/music.ogg".to_string(), 120.0)]),
+            playlist_order: PlaylistOrder::InOrder,
+            menu_music: None,
+            assist_mode: AssistMode::Off,
+            deadzone: 0.1,
+            invert_vertical: false,
+            brake_keys: [KeyCode::ArrowLeft, KeyCode::KeyA],
+            brake_min_speed: 0.4,
+            master_volume: 1.0,
+            music_volume: 0.5,
+            sfx_volume: 1.0,
+            pan_pickup_sounds: true,
+            camera_lead_x: 200.0,
+            camera_danger_nudge: true,
+            camera_bounds: None,
+            player_start: (0.0, 0.0),
+            combo_break_feedback: true,
+            combo_heal_milestones: vec![10],
+            second_wind_combo: Some(8),
+            difficulty_curve: DifficultyCurve::default(),
+            death_condition: DeathCondition::Health,
+            fuel_drain_rate: 3.0,
+            fuel_per_gem: 6.0,
+            graphics_quality: GraphicsQuality::High,
+            gem_y_distribution: YDistribution::Uniform,
+            beat_spawning: false,
+            drifting_gems: false,
+            gem_separation: 40.0,
+            despawn_margin: 1000.0,
+            present_mode: PresentMode::Fifo,
+            frame_cap: None,
+            coop: false,
+            time_of_day: true,
+        }
+    }
+}
+
+/// Accessibility assist: gems are collected from further away and hits do
+/// less damage. Assisted runs stay off the leaderboard.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AssistMode {
+    #[default]
+    Off,
+    On,
+}
+
+/// How the difficulty ramps up over a run, as keyframes of seconds into the
+/// run and the difficulty at that point, in order of time. The difficulty is
+/// interpolated between keyframes and holds at the last one.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DifficultyCurve(pub Vec<(f32, f32)>);
+
+impl Default for DifficultyCurve {
+    /// Grows by 0.02 per second for the first ten minutes
+    fn default() -> Self {
+        DifficultyCurve(vec![(0.0, 1.0), (600.0, 13.0)])
+    }
+}
+
+impl DifficultyCurve {
+    /// Difficulty `elapsed` seconds into a run
+    pub fn evaluate(&self, elapsed: f32) -> f32 {
+        let Some(&(first_time, first_difficulty)) = self.0.first() else {
+            return 1.0;
+        };
+        if elapsed <= first_time {
+            return first_difficulty;
+        }
+
+        for pair in self.0.windows(2) {
+            let ((from_time, from), (to_time, to)) = (pair[0], pair[1]);
+            if elapsed < to_time {
+                let t = (elapsed - from_time) / (to_time - from_time);
+                return from + (to - from) * t;
+            }
+        }
+        self.0.last().map_or(1.0, |&(_, difficulty)| difficulty)
+    }
+}
+
+/// The option `step` places along from `current` in `all`, wrapping around.
+/// An option missing from `all` counts as the first.
+fn cycle<T: Copy + PartialEq>(all: &[T], current: T, step: isize) -> T {
+    let index = all
+        .iter()
+        .position(|option| *option == current)
+        .unwrap_or_default() as isize;
+    all[(index + step).rem_euclid(all.len() as isize) as usize]
+}
+
+/// Ready-made difficulty curves to pick from on the settings screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifficultyPreset {
+    Relaxed,
+    Normal,
+    Hard,
+}
+
+impl DifficultyPreset {
+    const ALL: [DifficultyPreset; 3] = [
+        DifficultyPreset::Relaxed,
+        DifficultyPreset::Normal,
+        DifficultyPreset::Hard,
+    ];
+
+    pub fn curve(self) -> DifficultyCurve {
+        match self {
+            // half the default ramp, over the same ten minutes
+            DifficultyPreset::Relaxed => DifficultyCurve(vec![(0.0, 1.0), (600.0, 7.0)]),
+            DifficultyPreset::Normal => DifficultyCurve::default(),
+            // starts harder and gets there in five minutes
+            DifficultyPreset::Hard => DifficultyCurve(vec![(0.0, 2.0), (300.0, 14.0)]),
+        }
+    }
+
+    /// The preset `curve` is, or `None` for a curve edited by hand
+    pub fn matching(curve: &DifficultyCurve) -> Option<Self> {
+        DifficultyPreset::ALL
+            .into_iter()
+            .find(|preset| preset.curve() == *curve)
+    }
+
+    /// The preset `step` places along from `curve`, wrapping around. A curve
+    /// edited by hand counts as `Normal`.
+    pub fn cycled(curve: &DifficultyCurve, step: isize) -> Self {
+        let current = DifficultyPreset::matching(curve).unwrap_or(DifficultyPreset::Normal);
+        cycle(&DifficultyPreset::ALL, current, step)
+    }
+}
+
+/// How many purely cosmetic effects to draw, for slower machines
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GraphicsQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl GraphicsQuality {
+    const ALL: [GraphicsQuality; 3] = [
+        GraphicsQuality::Low,
+        GraphicsQuality::Medium,
+        GraphicsQuality::High,
+    ];
+
+    /// The quality `step` places along from this one, wrapping around
+    pub fn cycled(self, step: isize) -> Self {
+        cycle(&GraphicsQuality::ALL, self, step)
+    }
+
+    /// Spin, bob and pop in gems rather than drawing them still
+    pub fn animate_gems(self) -> bool {
+        self != GraphicsQuality::Low
+    }
+
+    /// Most gem icons flying to the scoreboard at once, `None` for no limit.
+    /// Points past the limit go straight onto the scoreboard.
+    pub fn max_flying_scores(self) -> Option<usize> {
+        match self {
+            GraphicsQuality::Low => Some(0),
+            GraphicsQuality::Medium => Some(8),
+            GraphicsQuality::High => None,
+        }
+    }
+}
+
+/// How frames are handed to the screen, trading power use against latency.
+/// Read once on startup.
+///
+/// Only `Fifo` is supported everywhere, so `Immediate` asks for bevy's
+/// `AutoNoVsync`, which falls back to one the platform has rather than
+/// failing to open the window. There is no `Mailbox`: bevy can only ask for
+/// it outright, with no fallback where the platform lacks it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentMode {
+    /// Vsync: waits for the screen, never tears
+    #[default]
+    Fifo,
+    /// No waiting, so the lowest latency, but may tear. Falls back to
+    /// mailbox, then `Fifo`.
+    Immediate,
+}
+
+impl PresentMode {
+    pub fn window_present_mode(self) -> WindowPresentMode {
+        match self {
+            PresentMode::Fifo => WindowPresentMode::Fifo,
+            PresentMode::Immediate => WindowPresentMode::AutoNoVsync,
+        }
+    }
+}
+
+/// Horizontal extent of a level of set length. The camera stops at the
+/// edges rather than showing beyond them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CameraBounds {
+    pub min_x: f32,
+    pub max_x: f32,
+}
+
+impl CameraBounds {
+    /// Closest camera position to `x` that keeps a view `half_width` either
+    /// side of it within the bounds. A level narrower than the view is
+    /// centered instead.
+    pub fn clamp(&self, x: f32, half_width: f32) -> f32 {
+        let (min, max) = (self.min_x + half_width, self.max_x - half_width);
+        if min > max {
+            return (self.min_x + self.max_x) / 2.0;
+        }
+        x.clamp(min, max)
+    }
+}
+
+/// Way a run ends on top of the rugs' health running out
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum DeathCondition {
+    /// Only health ends the run
+    #[default]
+    Health,
+    /// The rug burns fuel, and the run ends when the tank runs dry
+    Fuel,
+    /// The run ends after this many seconds
+    Timer(f32),
+}
+
+impl DeathCondition {
+    /// Conditions the settings screen cycles through. Its timer runs for
+    /// three minutes; other lengths can be set in the settings file.
+    const ALL: [DeathCondition; 3] = [
+        DeathCondition::Health,
+        DeathCondition::Fuel,
+        DeathCondition::Timer(180.0),
+    ];
+
+    /// The condition `step` places along from this one, wrapping around. A
+    /// timer of any length counts as the timer.
+    pub fn cycled(self, step: isize) -> Self {
+        let current = DeathCondition::ALL
+            .into_iter()
+            .find(|condition| mem::discriminant(condition) == mem::discriminant(&self))
+            .unwrap_or_default();
+        cycle(&DeathCondition::ALL, current, step)
+    }
+}
+
+/// How newly spawned gems cluster vertically
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YDistribution {
+    /// Equally likely at any height
+    #[default]
+    Uniform,
+    /// Mostly around the middle of the level
+    GaussianCenter,
+    /// Mostly towards the top and bottom
+    EdgeBiased,
+}
+
+/// How the rugs' health is shown on the HUD. The health bar is always shown.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HealthDisplayMode {
+    /// Current and max health as numbers, e.g. 2/3
+    #[default]
+    Numeric,
+    /// A heart per hit point, faded once lost
+    Hearts,
+    /// The health bar on its own
+    Bar,
+}
+
+impl HealthDisplayMode {
+    const ALL: [HealthDisplayMode; 3] = [
+        HealthDisplayMode::Numeric,
+        HealthDisplayMode::Hearts,
+        HealthDisplayMode::Bar,
+    ];
+
+    /// The mode `step` places along from this one, wrapping around
+    pub fn cycled(self, step: isize) -> Self {
+        cycle(&HealthDisplayMode::ALL, self, step)
+    }
+}
+
+/// How scores are written out
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreFormat {
+    /// Digits only, e.g. 12345
+    Plain,
+    /// Digits grouped in thousands, e.g. 12,345
+    #[default]
+    Separated,
+    /// Grouped in thousands, and shortened once the score gets large, e.g.
+    /// 12.3K
+    Abbreviated,
+}
+
+impl ScoreFormat {
+    const ALL: [ScoreFormat; 3] = [
+        ScoreFormat::Plain,
+        ScoreFormat::Separated,
+        ScoreFormat::Abbreviated,
+    ];
+
+    /// The format `step` places along from this one, wrapping around
+    pub fn cycled(self, step: isize) -> Self {
+        cycle(&ScoreFormat::ALL, self, step)
+    }
+}
+
+/// Order the music playlist is played in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaylistOrder {
+    #[default]
+    InOrder,
+    /// Shuffled again every time the playlist starts over
+    Shuffle,
+}
+
+impl AssistMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            AssistMode::Off => AssistMode::On,
+            AssistMode::On => AssistMode::Off,
+        }
+    }
+
+    /// Multiplier on the distance gems are collected from
+    pub fn pickup_radius_scale(self) -> f32 {
+        match self {
+            AssistMode::Off => 1.0,
+            AssistMode::On => 2.5,
+        }
+    }
+
+    /// Multiplier on the damage the player takes
+    pub fn damage_multiplier(self) -> f32 {
+        match self {
+            AssistMode::Off => 1.0,
+            AssistMode::On => 0.5,
+        }
+    }
+}
+
+impl Versioned for GameSettings {
+    const VERSION: u32 = 3;
+
+    fn version_mut(&mut self) -> &mut u32 {
+        &mut self.version
+    }
+
+    fn migrate(&mut self, from: u32) {
+        // version 3 replaced the health number toggle with display modes
+        if from < 3 && self.show_health_number == Some(false) {
+            self.health_display = HealthDisplayMode::Bar;
+        }
+    }
+}
+
+impl GameSettings {
+    pub fn load() -> Self {
+        load_versioned(SETTINGS_PATH)
+    }
+
+    pub fn save(&self) {
+        save_ron(SETTINGS_PATH, self);
+    }
+}
+
+/// Scales every sound, on top of its music or effects volume
+#[derive(Resource, Deref, Clone, Copy, PartialEq)]
+pub struct MasterVolume(pub f32);
+
+/// Scales the background music
+#[derive(Resource, Deref, Clone, Copy, PartialEq)]
+pub struct MusicVolume(pub f32);
+
+/// Scales pickups, hits and the other sound effects
+#[derive(Resource, Deref, Clone, Copy, PartialEq)]
+pub struct SfxVolume(pub f32);
+
+/// The volume resources, for systems that spawn or adjust sounds
+#[derive(SystemParam)]
+pub struct Volumes<'w> {
+    master: Res<'w, MasterVolume>,
+    music: Res<'w, MusicVolume>,
+    sfx: Res<'w, SfxVolume>,
+}
+
+impl Volumes<'_> {
+    /// Volume to play the background music at
+    pub fn music(&self) -> Volume {
+        Volume::new(**self.master * **self.music)
+    }
+
+    /// Volume to play sound effects at
+    pub fn sfx(&self) -> Volume {
+        Volume::new(**self.master * **self.sfx)
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.master.is_changed() || self.music.is_changed() || self.sfx.is_changed()
+    }
+}
+
+/// The volume resources, for the menus that change them
+#[derive(SystemParam)]
+pub struct VolumeLevels<'w> {
+    pub master: ResMut<'w, MasterVolume>,
+    pub music: ResMut<'w, MusicVolume>,
+    pub sfx: ResMut<'w, SfxVolume>,
+}
+
+/// Write changed volumes to the settings file
+fn save_volumes(volumes: Volumes, mut settings: ResMut<GameSettings>) {
+    // they were just read from the file
+    if !volumes.is_changed() || volumes.master.is_added() {
+        return;
+    }
+
+    settings.master_volume = **volumes.master;
+    settings.music_volume = **volumes.music;
+    settings.sfx_volume = **volumes.sfx;
+    settings.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn master_volume_scales_music_and_effects() {
+        let mut world = World::new();
+        world.insert_resource(MasterVolume(0.5));
+        world.insert_resource(MusicVolume(0.4));
+        world.insert_resource(SfxVolume(1.0));
+
+        let (music, sfx) = world
+            .run_system_once(|volumes: Volumes| (volumes.music().get(), volumes.sfx().get()))
+            .unwrap();
+        assert_eq!(music, 0.2);
+        assert_eq!(sfx, 0.5);
+    }
+
+    #[test]
+    fn difficulty_presets_cycle_both_ways_and_wrap() {
+        let normal = DifficultyPreset::Normal.curve();
+        assert_eq!(DifficultyPreset::cycled(&normal, 1), DifficultyPreset::Hard);
+        assert_eq!(
+            DifficultyPreset::cycled(&normal, -1),
+            DifficultyPreset::Relaxed
+        );
+        assert_eq!(
+            DifficultyPreset::cycled(&DifficultyPreset::Hard.curve(), 1),
+            DifficultyPreset::Relaxed
+        );
+
+        let custom = DifficultyCurve(vec![(0.0, 3.0)]);
+        assert_eq!(DifficultyPreset::matching(&custom), None);
+        assert_eq!(DifficultyPreset::cycled(&custom, 1), DifficultyPreset::Hard);
+    }
+
+    #[test]
+    fn options_cycle_both_ways_and_wrap() {
+        assert_eq!(GraphicsQuality::High.cycled(1), GraphicsQuality::Low);
+        assert_eq!(GraphicsQuality::Low.cycled(-1), GraphicsQuality::High);
+        assert_eq!(ScoreFormat::Plain.cycled(4), ScoreFormat::Separated);
+        // a timer of another length still moves on from the timer
+        assert_eq!(
+            DeathCondition::Timer(45.0).cycled(1),
+            DeathCondition::Health
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use arboard::Clipboard;
+use bevy::prelude::*;
+
+use crate::Stats;
+
+/// Level units in one meter of the distance shown to players
+const PIXELS_PER_METER: f32 = 10.0;
+
+/// One-line summary of a finished run, for pasting into a chat
+pub fn share_text(score: usize, stats: &Stats, seed: u64) -> String {
+    format!(
+        "Magic Rug — Score {score}, {} gems, {:.0}m, seed {seed}",
+        stats.gems_collected,
+        stats.distance / PIXELS_PER_METER
+    )
+}
+
+/// The system clipboard, opened on the first copy and kept for the rest of
+/// the session. On Linux the copied text is only served while the clipboard
+/// that set it is alive, so it can't be dropped straight after copying.
+/// Non-send, as some platforms tie the clipboard to the thread that opened it.
+#[derive(Default)]
+pub struct SystemClipboard(Option<Clipboard>);
+
+impl SystemClipboard {
+    /// Put `text` on the system clipboard. Some platforms have no clipboard
+    /// to use, so failing is logged rather than treated as an error.
+    pub fn copy(&mut self, text: &str) -> bool {
+        let clipboard = match self.0.as_mut() {
+            Some(clipboard) => clipboard,
+            None => match Clipboard::new() {
+                Ok(clipboard) => self.0.insert(clipboard),
+                Err(err) => {
+                    warn!("failed to open the clipboard: {err}");
+                    return false;
+                }
+            },
+        };
+
+        match clipboard.set_text(text) {
+            Ok(()) => true,
+            Err(err) => {
+                warn!("failed to copy the results to the clipboard: {err}");
+                false
+            }
+        }
+    }
+}
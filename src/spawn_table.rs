@@ -0,0 +1,243 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{gems::GemKind, save::load_ron};
+
+/// Spawn table designers can edit, relative to the working directory
+const SPAWN_TABLE_PATH: &str = "assets/spawn_table.ron";
+/// Copy of the shipped table, used when the file is missing or invalid
+const DEFAULT_SPAWN_TABLE: &str = include_str!("../assets/spawn_table.ron");
+
+/// Plugin to load the spawn table on startup
+pub struct SpawnTablePlugin;
+
+impl Plugin for SpawnTablePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SpawnTable::load());
+    }
+}
+
+/// What gets spawned into a run and how often
+#[derive(Resource, Serialize, Deserialize, Debug, Clone)]
+pub struct SpawnTable {
+    pub gems: Vec<GemWeight>,
+    pub split_gem_chance: f32,
+    pub rewind_gem_chance: f32,
+    pub freeze_gem_chance: f32,
+    pub double_points_chance: f32,
+    pub extra_life_chance: f32,
+    pub obstacle_interval: f32,
+    pub wall_interval: f32,
+    pub heavy_obstacle_chance: f32,
+    pub safe_lane_interval: f32,
+}
+
+/// Effect a streamed gem can carry on top of its kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GemPower {
+    Split,
+    Rewind,
+    Freeze,
+    DoublePoints,
+    ExtraLife,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GemWeight {
+    pub kind: GemKind,
+    pub weight: f32,
+    /// Points the kind is worth, `None` for its usual value
+    #[serde(default)]
+    pub value: Option<usize>,
+}
+
+impl Default for SpawnTable {
+    fn default() -> Self {
+        ron::from_str(DEFAULT_SPAWN_TABLE).expect("the embedded spawn table is valid")
+    }
+}
+
+impl SpawnTable {
+    pub fn load() -> Self {
+        let table: SpawnTable = load_ron(SPAWN_TABLE_PATH);
+        if let Err(err) = table.validate() {
+            warn!("ignoring invalid {SPAWN_TABLE_PATH}: {err}");
+            return SpawnTable::default();
+        }
+        table
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for entry in &self.gems {
+            if entry.weight.is_nan() || entry.weight < 0.0 {
+                return Err(format!("{:?} has weight {}", entry.kind, entry.weight));
+            }
+        }
+        if self.gems.iter().map(|entry| entry.weight).sum::<f32>() <= 0.0 {
+            return Err("no gem has a positive weight".into());
+        }
+        let chances = [
+            ("split gem", self.split_gem_chance),
+            ("rewind gem", self.rewind_gem_chance),
+            ("freeze gem", self.freeze_gem_chance),
+            ("double points", self.double_points_chance),
+            ("extra life", self.extra_life_chance),
+            ("heavy obstacle", self.heavy_obstacle_chance),
+        ];
+        for (name, chance) in chances {
+            if !(0.0..=1.0).contains(&chance) {
+                return Err(format!("{name} chance {chance} isn't between 0 and 1"));
+            }
+        }
+        let total: f32 = self.power_chances().iter().map(|(_, chance)| chance).sum();
+        if total > 1.0 {
+            return Err(format!("gem power chances add up to {total}, more than 1"));
+        }
+        if [
+            self.obstacle_interval,
+            self.wall_interval,
+            self.safe_lane_interval,
+        ]
+        .iter()
+        .any(|interval| interval.is_nan() || *interval <= 0.0)
+        {
+            return Err("spawn intervals must be positive".into());
+        }
+        Ok(())
+    }
+
+    /// Points a gem of `kind` is worth in this table
+    pub fn gem_value(&self, kind: GemKind) -> usize {
+        self.gems
+            .iter()
+            .find(|entry| entry.kind == kind)
+            .and_then(|entry| entry.value)
+            .unwrap_or_else(|| kind.value())
+    }
+
+    /// Pick a gem kind, weighted by the table. Mimics get more common as the
+    /// difficulty goes up.
+    pub fn random_gem(&self, rng: &mut impl Rng, difficulty: f32) -> GemKind {
+        let weight = |entry: &GemWeight| match entry.kind {
+            GemKind::Mimic => entry.weight * difficulty,
+            _ => entry.weight,
+        };
+
+        let total: f32 = self.gems.iter().map(weight).sum();
+        let mut roll = rng.random::<f32>() * total;
+        for entry in &self.gems {
+            if roll < weight(entry) {
+                return entry.kind;
+            }
+            roll -= weight(entry);
+        }
+        GemKind::Common
+    }
+
+    fn power_chances(&self) -> [(GemPower, f32); 5] {
+        [
+            (GemPower::Split, self.split_gem_chance),
+            (GemPower::Rewind, self.rewind_gem_chance),
+            (GemPower::Freeze, self.freeze_gem_chance),
+            (GemPower::DoublePoints, self.double_points_chance),
+            (GemPower::ExtraLife, self.extra_life_chance),
+        ]
+    }
+
+    /// Pick the power a streamed gem carries, if any. One roll covers them
+    /// all, so each power turns up at exactly its chance.
+    pub fn random_power(&self, rng: &mut impl Rng) -> Option<GemPower> {
+        let mut roll = rng.random::<f32>();
+        for (power, chance) in self.power_chances() {
+            if roll < chance {
+                return Some(power);
+            }
+            roll -= chance;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn gem_values_fall_back_to_the_kind() {
+        let table = SpawnTable {
+            gems: vec![
+                GemWeight {
+                    kind: GemKind::Common,
+                    weight: 1.0,
+                    value: Some(3),
+                },
+                GemWeight {
+                    kind: GemKind::Rare,
+                    weight: 1.0,
+                    value: None,
+                },
+            ],
+            ..default()
+        };
+
+        assert_eq!(table.gem_value(GemKind::Common), 3);
+        assert_eq!(table.gem_value(GemKind::Rare), GemKind::Rare.value());
+        // left out of the table altogether
+        assert_eq!(
+            table.gem_value(GemKind::Precious),
+            GemKind::Precious.value()
+        );
+    }
+
+    #[test]
+    fn chances_outside_zero_to_one_are_invalid() {
+        assert!(SpawnTable::default().validate().is_ok());
+
+        let table = SpawnTable {
+            split_gem_chance: 1.5,
+            ..default()
+        };
+        assert_eq!(
+            table.validate(),
+            Err("split gem chance 1.5 isn't between 0 and 1".to_string())
+        );
+    }
+
+    #[test]
+    fn powers_turn_up_at_their_chance() {
+        let table = SpawnTable {
+            split_gem_chance: 0.25,
+            rewind_gem_chance: 0.5,
+            ..default()
+        };
+        assert!(table.validate().is_ok());
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let rolls: Vec<_> = (0..4000).map(|_| table.random_power(&mut rng)).collect();
+        let share = |power| {
+            rolls.iter().filter(|rolled| **rolled == power).count() as f32 / rolls.len() as f32
+        };
+        assert!((share(Some(GemPower::Split)) - 0.25).abs() < 0.03);
+        assert!((share(Some(GemPower::Rewind)) - 0.5).abs() < 0.03);
+        let none: f32 = 1.0
+            - table
+                .power_chances()
+                .iter()
+                .map(|(_, chance)| chance)
+                .sum::<f32>();
+        assert!((share(None) - none).abs() < 0.03);
+    }
+
+    #[test]
+    fn power_chances_over_one_are_invalid() {
+        let table = SpawnTable {
+            split_gem_chance: 0.6,
+            rewind_gem_chance: 0.6,
+            ..default()
+        };
+        assert!(table.validate().is_err());
+    }
+}
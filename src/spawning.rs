@@ -0,0 +1,523 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    aabb_overlap,
+    beats::spawns_on_beats,
+    double_points::DoublePoints,
+    extra_life::ExtraLife,
+    freeze::FreezeGem,
+    gems::{Charge, GemBob, GemKind, GemValue, SpawnAnim},
+    obstacles::{Hitbox, MovingWall, Obstacle, SafeLane, Telegraph},
+    rewind::RewindGem,
+    settings::{GameSettings, YDistribution},
+    spawn_table::{GemPower, SpawnTable},
+    split::SplitGem,
+    Collider, CollisionEvent, Difficulty, GameRng, GameplaySet, Gem, MainCamera, RunEntity,
+    Velocity, GEM_SIZE, GEM_Z,
+};
+
+/// Where the first gem of a run is placed
+const FIRST_GEM_X: f32 = 600.0;
+/// Horizontal distance between gems at normal density
+const GEM_SPACING: f32 = 300.0;
+/// Gems are spawned up to this far ahead of the camera center, so they
+/// appear off screen even when zoomed out
+pub const GEM_SPAWN_AHEAD: f32 = 1500.0;
+
+/// How much each pickup or miss moves the rolling collection ratio
+const COLLECTION_RATIO_WEIGHT: f32 = 0.1;
+/// How strongly the collection ratio changes gem density, around a ratio of
+/// one half
+const DENSITY_GAIN: f32 = 1.0;
+const MIN_GEM_DENSITY: f32 = 0.75;
+const MAX_GEM_DENSITY: f32 = 1.5;
+/// Gems are streamed in at `MAX_GEM_DENSITY`, then thinned out to the
+/// current density once they come this close to the camera center, so the
+/// latest collection ratio decides how busy the screen gets
+const THIN_DISTANCE: f32 = 1000.0;
+
+/// Gems spawn at most this far above or below the middle of the level
+const GEM_HEIGHT_RANGE: f32 = 200.0;
+/// Standard deviation of `YDistribution::GaussianCenter`, as a fraction of
+/// `GEM_HEIGHT_RANGE`
+const GAUSSIAN_GEM_SPREAD: f32 = 0.35;
+
+/// Fastest a drifting gem moves up or down, in units per second
+const GEM_DRIFT_SPEED: f32 = 80.0;
+
+/// Room kept between gems and obstacles, so every gem can be collected
+/// without taking a hit
+const OBSTACLE_MARGIN: f32 = 30.0;
+/// Heights tried when placing a gem or obstacle clear of the other, before
+/// settling for the last one. Placement is best effort: when nothing clear
+/// turns up, the last height is used anyway so the spawns keep their rhythm.
+pub const PLACEMENT_ATTEMPTS: usize = 8;
+
+/// Plugin for streaming gems in ahead of the player and removing what has
+/// been left behind
+pub struct SpawningPlugin;
+
+impl Plugin for SpawningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GemStream>()
+            .init_resource::<CollectionRatio>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    (stream_gems.run_if(not(spawns_on_beats)), thin_gems)
+                        .chain()
+                        .in_set(GameplaySet::Spawn),
+                    move_gems.in_set(GameplaySet::Movement),
+                    (track_collections, despawn_offscreen)
+                        .chain()
+                        .in_set(GameplaySet::Collision),
+                ),
+            );
+    }
+}
+
+/// Position of the next gem to stream in
+#[derive(Resource)]
+pub struct GemStream {
+    next_x: f32,
+    /// How many gems have been streamed in this run
+    count: u64,
+}
+
+impl Default for GemStream {
+    fn default() -> Self {
+        GemStream {
+            next_x: FIRST_GEM_X,
+            count: 0,
+        }
+    }
+}
+
+/// Streamed gem that hasn't been through thinning yet, with its place in the
+/// stream
+#[derive(Component)]
+struct Unthinned(u64);
+
+/// Gem moving by its `Velocity`, bouncing off the top and bottom of the
+/// level
+#[derive(Component)]
+struct Drifting;
+
+/// Rolling fraction of recent gems that were collected rather than missed
+#[derive(Resource, Deref, DerefMut)]
+pub struct CollectionRatio(f32);
+
+impl Default for CollectionRatio {
+    fn default() -> Self {
+        CollectionRatio(0.5)
+    }
+}
+
+impl CollectionRatio {
+    fn record(&mut self, collected: bool) {
+        let sample = if collected { 1.0 } else { 0.0 };
+        self.0 += (sample - self.0) * COLLECTION_RATIO_WEIGHT;
+    }
+
+    /// Gem density multiplier: denser for players collecting most gems,
+    /// sparser for those missing many, so the field stays readable
+    fn gem_density(&self) -> f32 {
+        (1.0 + (self.0 - 0.5) * DENSITY_GAIN).clamp(MIN_GEM_DENSITY, MAX_GEM_DENSITY)
+    }
+}
+
+/// Height of a newly spawned gem, spread out as `distribution` says
+pub fn gem_height(rng: &mut impl Rng, distribution: YDistribution) -> f32 {
+    let uniform = rng.random::<f32>() * 2.0 - 1.0;
+    let offset = match distribution {
+        YDistribution::Uniform => uniform,
+        YDistribution::GaussianCenter => {
+            // Box-Muller, with the first sample kept away from 0 for the log
+            let radius = (-2.0 * (1.0 - rng.random::<f32>()).ln()).sqrt();
+            let normal = radius * (std::f32::consts::TAU * uniform).cos();
+            (normal * GAUSSIAN_GEM_SPREAD).clamp(-1.0, 1.0)
+        }
+        YDistribution::EdgeBiased => uniform.signum() * uniform.abs().sqrt(),
+    };
+    offset * GEM_HEIGHT_RANGE
+}
+
+/// Whether a gem at `gem` is at least `OBSTACLE_MARGIN` away from an
+/// obstacle at `obstacle` with the given hitbox
+pub fn clear_of_obstacle(gem: Vec2, obstacle: Vec2, hitbox: Vec2) -> bool {
+    let gem_area = Vec2::splat(GEM_SIZE + 2.0 * OBSTACLE_MARGIN);
+    !aabb_overlap(gem, gem_area, obstacle, hitbox)
+}
+
+/// Obstacles gems are kept clear of, including the ones still being
+/// telegraphed. Walls are left out: their gap moves, so there is no fixed
+/// place to avoid.
+#[derive(SystemParam)]
+pub struct Hazards<'w, 's> {
+    #[allow(clippy::type_complexity)]
+    obstacles:
+        Query<'w, 's, (&'static Transform, &'static Hitbox), (With<Obstacle>, Without<MovingWall>)>,
+    telegraphs: Query<'w, 's, (&'static Transform, &'static Telegraph)>,
+}
+
+impl Hazards<'_, '_> {
+    /// Center and hitbox size of each hazard
+    pub fn areas(&self) -> Vec<(Vec2, Vec2)> {
+        self.obstacles
+            .iter()
+            .map(|(transform, hitbox)| (transform.translation.truncate(), **hitbox))
+            .chain(self.telegraphs.iter().map(|(transform, telegraph)| {
+                (transform.translation.truncate(), *telegraph.hitbox())
+            }))
+            .collect()
+    }
+}
+
+/// Whether a gem at `gem` is clear of every hazard in `areas`, as returned
+/// by `Hazards::areas`
+pub fn clear_of_hazards(gem: Vec2, areas: &[(Vec2, Vec2)]) -> bool {
+    areas
+        .iter()
+        .all(|(center, hitbox)| clear_of_obstacle(gem, *center, *hitbox))
+}
+
+/// Whether a gem at `gem` is at least `separation` away from every gem at
+/// `others`
+pub fn clear_of_gems(gem: Vec2, others: &[Vec2], separation: f32) -> bool {
+    others
+        .iter()
+        .all(|other| other.distance_squared(gem) >= separation * separation)
+}
+
+/// Everything a collectable gem is spawned with
+#[derive(Bundle)]
+pub struct GemBundle {
+    sprite: Sprite,
+    transform: Transform,
+    gem: Gem,
+    kind: GemKind,
+    value: GemValue,
+    bob: GemBob,
+    charge: Charge,
+    collider: Collider,
+    run_entity: RunEntity,
+}
+
+impl GemBundle {
+    /// Gem of `kind` at `position`, worth what its kind is
+    pub fn new(asset_server: &AssetServer, position: Vec2, kind: GemKind) -> Self {
+        GemBundle {
+            sprite: Sprite {
+                image: asset_server.load("sprites/gem.png"),
+                custom_size: Some(Vec2::new(GEM_SIZE, GEM_SIZE)),
+                color: kind.color(),
+                ..default()
+            },
+            transform: Transform::from_translation(position.extend(GEM_Z)),
+            gem: Gem,
+            kind,
+            value: GemValue(kind.value()),
+            // phase from the position so neighbouring gems don't bob in step
+            bob: GemBob::new(position.x),
+            charge: Charge::default(),
+            collider: Collider,
+            run_entity: RunEntity,
+        }
+    }
+
+    /// Make the gem worth `value` points rather than its kind's value
+    pub fn with_value(mut self, value: usize) -> Self {
+        self.value = GemValue(value);
+        self
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn stream_gems(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut stream: ResMut<GemStream>,
+    table: Res<SpawnTable>,
+    difficulty: Res<Difficulty>,
+    mut rng: ResMut<GameRng>,
+    camera_transform: Single<&Transform, With<MainCamera>>,
+    hazards: Hazards,
+    gems: Query<&Transform, With<Gem>>,
+    settings: Res<GameSettings>,
+) {
+    let _span = debug_span!("stream_gems").entered();
+    let spawn_until = camera_transform.translation.x + GEM_SPAWN_AHEAD;
+    let hazards = hazards.areas();
+    // gems spawned this tick are added as they go, as the query can't see them
+    let mut placed: Vec<Vec2> = gems
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+
+    while stream.next_x < spawn_until {
+        let distribution = settings.gem_y_distribution;
+        let mut position = Vec2::new(stream.next_x, gem_height(&mut rng.rng, distribution));
+        for _ in 1..PLACEMENT_ATTEMPTS {
+            let clear = clear_of_hazards(position, &hazards)
+                && clear_of_gems(position, &placed, settings.gem_separation);
+            if clear {
+                break;
+            }
+            position.y = gem_height(&mut rng.rng, distribution);
+        }
+        placed.push(position);
+        let kind = table.random_gem(&mut rng.rng, **difficulty);
+        let gem = commands
+            .spawn(GemBundle::new(&asset_server, position, kind).with_value(table.gem_value(kind)))
+            .id();
+        commands.entity(gem).insert(Unthinned(stream.count));
+        stream.count += 1;
+        if settings.drifting_gems {
+            let speed = rng.random_range(-GEM_DRIFT_SPEED..=GEM_DRIFT_SPEED);
+            commands
+                .entity(gem)
+                .insert((Drifting, Velocity(Vec2::new(0.0, speed))));
+        }
+        if settings.graphics_quality.animate_gems() {
+            commands
+                .entity(gem)
+                .insert(SpawnAnim::new(Vec2::splat(GEM_SIZE)));
+        }
+        if kind != GemKind::Mimic {
+            if let Some(power) = table.random_power(&mut rng.rng) {
+                let mut gem = commands.entity(gem);
+                match power {
+                    GemPower::Split => gem.insert(SplitGem),
+                    GemPower::Rewind => gem.insert(RewindGem),
+                    GemPower::Freeze => gem.insert(FreezeGem),
+                    GemPower::DoublePoints => gem.insert(DoublePoints),
+                    GemPower::ExtraLife => gem.insert(ExtraLife),
+                };
+            }
+        }
+
+        stream.next_x += GEM_SPACING / MAX_GEM_DENSITY;
+    }
+}
+
+/// Move drifting gems, turning them back once they pass the edge of the
+/// height gems spawn in
+fn move_gems(mut gems: Query<(&mut Transform, &mut Velocity), With<Drifting>>, time: Res<Time>) {
+    for (mut transform, mut velocity) in &mut gems {
+        transform.translation += velocity.extend(0.0) * time.delta_secs();
+
+        let y = transform.translation.y;
+        if (y > GEM_HEIGHT_RANGE && velocity.y > 0.0) || (y < -GEM_HEIGHT_RANGE && velocity.y < 0.0)
+        {
+            velocity.y = -velocity.y;
+        }
+    }
+}
+
+/// Roll from 0 to 1 deciding whether the gem at `index` in the stream of the
+/// run seeded with `seed` is thinned out. It doesn't draw from the run's
+/// generator, as how many gems are thinned on a tick depends on the frame
+/// rate, and that would shift the rest of the layout.
+fn thinning_roll(seed: u64, index: u64) -> f32 {
+    // spread consecutive indices across the seed's bits
+    StdRng::seed_from_u64(seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15)).random()
+}
+
+/// Remove some of the gems the camera is getting close to, leaving
+/// `gem_density` of the full stream. Thinned gems don't count as missed.
+fn thin_gems(
+    mut commands: Commands,
+    ratio: Res<CollectionRatio>,
+    rng: Res<GameRng>,
+    camera_transform: Single<&Transform, With<MainCamera>>,
+    gems: Query<(Entity, &Transform, &Unthinned)>,
+) {
+    let keep_chance = ratio.gem_density() / MAX_GEM_DENSITY;
+    let thin_before = camera_transform.translation.x + THIN_DISTANCE;
+
+    for (entity, transform, unthinned) in &gems {
+        if transform.translation.x >= thin_before {
+            continue;
+        }
+
+        if thinning_roll(rng.seed, unthinned.0) < keep_chance {
+            commands.entity(entity).remove::<Unthinned>();
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn track_collections(
+    mut collisions: EventReader<CollisionEvent>,
+    mut ratio: ResMut<CollectionRatio>,
+) {
+    for _ in collisions.read() {
+        ratio.record(true);
+    }
+}
+
+/// Remove gems, obstacles and safe lanes that scrolled out behind the
+/// camera, counting the gems as missed
+#[allow(clippy::type_complexity)]
+fn despawn_offscreen(
+    mut commands: Commands,
+    mut ratio: ResMut<CollectionRatio>,
+    camera: Single<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    query: Query<(Entity, &Transform, Has<Gem>), Or<(With<Gem>, With<Obstacle>, With<SafeLane>)>>,
+    settings: Res<GameSettings>,
+) {
+    // measured from the left edge of the view, so zooming out doesn't bring
+    // culled gems back into sight
+    let (camera_transform, projection) = *camera;
+    let view_left = camera_transform.translation.x - projection.area.width() / 2.0;
+    let despawn_before = view_left - settings.despawn_margin;
+
+    for (entity, transform, is_gem) in &query {
+        if transform.translation.x >= despawn_before {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+        if is_gem {
+            ratio.record(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn clear_gems_never_overlap_the_obstacle() {
+        let obstacle = Vec2::new(100.0, -20.0);
+        let hitbox = Vec2::new(60.0, 120.0);
+        let gem_size = Vec2::splat(GEM_SIZE);
+
+        let mut clear = 0;
+        for x in (-100..=300).step_by(5) {
+            for y in (-200..=200).step_by(5) {
+                let gem = Vec2::new(x as f32, y as f32);
+                if clear_of_obstacle(gem, obstacle, hitbox) {
+                    clear += 1;
+                    assert!(!aabb_overlap(gem, gem_size, obstacle, hitbox));
+                }
+            }
+        }
+        assert!(clear > 0);
+        assert!(!clear_of_obstacle(obstacle, obstacle, hitbox));
+    }
+
+    #[test]
+    fn thinning_leaves_the_run_generator_alone() {
+        let mut world = World::new();
+        world.insert_resource(GameRng::from_seed(5));
+        world.init_resource::<CollectionRatio>();
+        world.spawn((MainCamera, Transform::default()));
+        let gems: Vec<Entity> = (0..20)
+            .map(|i| world.spawn((Transform::default(), Unthinned(i))).id())
+            .collect();
+
+        world.run_system_once(thin_gems).unwrap();
+
+        let mut after = world.resource::<GameRng>().rng.clone();
+        let mut fresh = GameRng::from_seed(5).rng;
+        assert_eq!(after.random::<u64>(), fresh.random::<u64>());
+        // the same gems go every time
+        let kept: Vec<bool> = gems
+            .iter()
+            .map(|gem| world.get_entity(*gem).is_ok())
+            .collect();
+        let keep_chance = CollectionRatio::default().gem_density() / MAX_GEM_DENSITY;
+        let expected: Vec<bool> = (0..20).map(|i| thinning_roll(5, i) < keep_chance).collect();
+        assert_eq!(kept, expected);
+        assert!(kept.contains(&true) && kept.contains(&false));
+    }
+
+    #[test]
+    fn gems_closer_than_the_separation_are_not_clear() {
+        let others = [Vec2::new(0.0, 0.0), Vec2::new(100.0, 0.0)];
+
+        assert!(clear_of_gems(Vec2::new(50.0, 0.0), &others, 50.0));
+        assert!(!clear_of_gems(Vec2::new(50.0, 0.0), &others, 50.1));
+        assert!(!clear_of_gems(Vec2::new(95.0, 10.0), &others, 40.0));
+        assert!(clear_of_gems(Vec2::ZERO, &[], 40.0));
+    }
+
+    /// Fraction of gem heights drawn from `distribution` that land in the
+    /// middle half of the range
+    fn central_fraction(distribution: YDistribution) -> f32 {
+        let mut rng = StdRng::seed_from_u64(7);
+        let samples = 2000;
+        let central = (0..samples)
+            .map(|_| gem_height(&mut rng, distribution))
+            .inspect(|height| assert!(height.abs() <= GEM_HEIGHT_RANGE))
+            .filter(|height| height.abs() < GEM_HEIGHT_RANGE / 2.0)
+            .count();
+        central as f32 / samples as f32
+    }
+
+    #[test]
+    fn gaussian_gems_cluster_near_the_center() {
+        let uniform = central_fraction(YDistribution::Uniform);
+        let gaussian = central_fraction(YDistribution::GaussianCenter);
+        assert!((uniform - 0.5).abs() < 0.05);
+        assert!(gaussian > 0.75);
+        assert!(central_fraction(YDistribution::EdgeBiased) < uniform);
+    }
+
+    #[test]
+    fn gem_bundles_carry_their_value() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>();
+        let asset_server = app.world().resource::<AssetServer>().clone();
+        let world = app.world_mut();
+
+        let plain = world
+            .spawn(GemBundle::new(&asset_server, Vec2::ZERO, GemKind::Rare))
+            .id();
+        let from_table = world
+            .spawn(GemBundle::new(&asset_server, Vec2::ZERO, GemKind::Rare).with_value(12))
+            .id();
+
+        assert_eq!(
+            world.get::<GemValue>(plain).unwrap().0,
+            GemKind::Rare.value()
+        );
+        assert_eq!(world.get::<GemValue>(from_table).unwrap().0, 12);
+    }
+
+    #[test]
+    fn gems_just_inside_the_margin_are_kept() {
+        let mut world = World::new();
+        let settings = GameSettings::default();
+        world.init_resource::<CollectionRatio>();
+        world.spawn((
+            MainCamera,
+            Transform::default(),
+            OrthographicProjection {
+                area: Rect::new(-640.0, -360.0, 640.0, 360.0),
+                ..OrthographicProjection::default_2d()
+            },
+        ));
+        let despawn_before = -640.0 - settings.despawn_margin;
+        world.insert_resource(settings);
+        let inside = world
+            .spawn((Gem, Transform::from_xyz(despawn_before + 1.0, 0.0, 0.0)))
+            .id();
+        let outside = world
+            .spawn((Gem, Transform::from_xyz(despawn_before - 1.0, 0.0, 0.0)))
+            .id();
+
+        world.run_system_once(despawn_offscreen).unwrap();
+
+        assert!(world.get_entity(inside).is_ok());
+        assert!(world.get_entity(outside).is_err());
+        assert!(world.resource::<CollectionRatio>().0 < CollectionRatio::default().0);
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    collect_gems, gems::GemKind, spawn_table::SpawnTable, spawning::GemBundle, GameRng,
+    GameplaySet, Velocity, GEM_Z,
+};
+
+/// Split gems are drawn this much bigger than normal ones
+const SPLIT_GEM_SCALE: f32 = 1.5;
+
+/// Number of shards a split gem bursts into
+const SHARD_COUNT: usize = 5;
+const SHARD_SCALE: f32 = 0.6;
+const SHARD_SPEED: f32 = 350.0;
+/// Fraction of a shard's speed lost per second
+const SHARD_DRAG: f32 = 1.5;
+/// Shards that haven't been collected by then disappear
+const SHARD_LIFETIME: f32 = 2.0;
+
+/// Plugin for gems that burst into collectable shards
+pub struct SplitPlugin;
+
+impl Plugin for SplitPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SplitGemCollected>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    (move_shards, expire_shards).in_set(GameplaySet::Movement),
+                    split_gem.after(collect_gems).in_set(GameplaySet::Collision),
+                ),
+            )
+            .add_systems(Update, scale_split_gems);
+    }
+}
+
+/// A gem that bursts into several smaller ones when collected
+#[derive(Component)]
+pub struct SplitGem;
+
+/// A split gem was collected at `position`
+#[derive(Event)]
+pub struct SplitGemCollected {
+    pub position: Vec2,
+}
+
+/// Small gem flying out of a split gem, gone once the timer runs out
+#[derive(Component, Deref, DerefMut)]
+struct Shard(Timer);
+
+fn scale_split_gems(mut gems: Query<&mut Transform, Added<SplitGem>>) {
+    for mut transform in &mut gems {
+        transform.scale = Vec3::splat(SPLIT_GEM_SCALE);
+    }
+}
+
+/// Burst every collected split gem into shards flying out evenly, starting
+/// at a random angle
+fn split_gem(
+    mut commands: Commands,
+    mut events: EventReader<SplitGemCollected>,
+    asset_server: Res<AssetServer>,
+    table: Res<SpawnTable>,
+    mut rng: ResMut<GameRng>,
+) {
+    for event in events.read() {
+        let start_angle = rng.random::<f32>() * std::f32::consts::TAU;
+
+        for i in 0..SHARD_COUNT {
+            let angle = start_angle + std::f32::consts::TAU * i as f32 / SHARD_COUNT as f32;
+            commands
+                .spawn(
+                    GemBundle::new(&asset_server, event.position, GemKind::Common)
+                        .with_value(table.gem_value(GemKind::Common)),
+                )
+                .insert((
+                    Transform {
+                        translation: event.position.extend(GEM_Z),
+                        scale: Vec3::splat(SHARD_SCALE),
+                        ..default()
+                    },
+                    Velocity(Vec2::from_angle(angle) * SHARD_SPEED),
+                    Shard(Timer::from_seconds(SHARD_LIFETIME, TimerMode::Once)),
+                ));
+        }
+    }
+}
+
+fn move_shards(mut shards: Query<(&mut Transform, &mut Velocity), With<Shard>>, time: Res<Time>) {
+    let drag = (1.0 - SHARD_DRAG * time.delta_secs()).max(0.0);
+
+    for (mut transform, mut velocity) in &mut shards {
+        transform.translation += velocity.extend(0.0) * time.delta_secs();
+        **velocity *= drag;
+    }
+}
+
+fn expire_shards(mut commands: Commands, mut shards: Query<(Entity, &mut Shard)>, time: Res<Time>) {
+    for (entity, mut shard) in &mut shards {
+        if shard.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+
+use crate::{
+    menu::{spawn_main_menu, MainMenuUi},
+    obstacles::PlayerHurt,
+    save::SaveData,
+    settings::{AssistMode, GameSettings},
+    CollisionEvent, GameState, GameplaySet, HudText, RunFlags, TEXT_COLOR,
+};
+
+/// Plugin to track the longest run of gems collected without getting hit
+pub struct StreakPlugin;
+
+impl Plugin for StreakPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NoHitStreak>()
+            .add_systems(
+                FixedUpdate,
+                update_no_hit_streak.after(GameplaySet::Collision),
+            )
+            .add_systems(OnEnter(GameState::GameOver), record_no_hit_streak)
+            .add_systems(
+                OnEnter(GameState::MainMenu),
+                show_no_hit_record.after(spawn_main_menu),
+            );
+    }
+}
+
+/// Gems collected without getting hit in the current run
+#[derive(Resource, Default)]
+pub struct NoHitStreak {
+    current: usize,
+    best: usize,
+}
+
+fn update_no_hit_streak(
+    mut collisions: EventReader<CollisionEvent>,
+    mut hurts: EventReader<PlayerHurt>,
+    mut streak: ResMut<NoHitStreak>,
+) {
+    // a hit in the same tick as a pickup still ends the streak
+    streak.current += collisions.read().count();
+    if hurts.read().count() > 0 {
+        streak.current = 0;
+    }
+    streak.best = streak.best.max(streak.current);
+}
+
+/// Keep the run's best streak if it beats the record. Assisted and cheated
+/// runs don't count, like on the leaderboard.
+fn record_no_hit_streak(
+    streak: Res<NoHitStreak>,
+    settings: Res<GameSettings>,
+    flags: Res<RunFlags>,
+    mut save: ResMut<SaveData>,
+) {
+    if settings.assist_mode == AssistMode::On
+        || flags.cheated
+        || streak.best <= save.best_no_hit_streak
+    {
+        return;
+    }
+
+    save.best_no_hit_streak = streak.best;
+    save.save();
+}
+
+fn show_no_hit_record(
+    mut commands: Commands,
+    save: Res<SaveData>,
+    menu: Single<Entity, With<MainMenuUi>>,
+) {
+    if save.best_no_hit_streak == 0 {
+        return;
+    }
+
+    commands.entity(*menu).with_child((
+        Text::new(format!(
+            "Longest no-hit streak: {} gems",
+            save.best_no_hit_streak
+        )),
+        TextFont::default(),
+        HudText(0.75),
+        TextColor(TEXT_COLOR),
+    ));
+}
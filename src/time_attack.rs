@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+
+use crate::{
+    save::SaveData,
+    settings::{AssistMode, GameSettings},
+    setup, GameMode, GameState, GameplaySet, HudText, RunFlags, SideHud, Stats, TEXT_COLOR,
+};
+
+/// Gems to collect to finish a time attack
+pub const TIME_ATTACK_GEMS: usize = 100;
+
+/// Plugin for the time attack mode, where the run ends once enough gems are
+/// collected and the time taken is the result
+pub struct TimeAttackPlugin;
+
+impl Plugin for TimeAttackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_time_attack_ui.after(setup))
+            .add_systems(
+                FixedUpdate,
+                finish_time_attack
+                    .after(GameplaySet::Collision)
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(is_time_attack),
+            )
+            .add_systems(Update, update_time_attack_ui)
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                (record_time_attack, show_time_attack_result)
+                    .chain()
+                    .run_if(is_time_attack),
+            );
+    }
+}
+
+#[derive(Component)]
+struct TimeAttackUi;
+
+fn is_time_attack(mode: Res<GameMode>) -> bool {
+    matches!(*mode, GameMode::TimeAttack(_))
+}
+
+/// Whether the run ended by collecting every gem rather than dying
+pub fn finished(mode: &GameMode, stats: &Stats) -> bool {
+    matches!(mode, GameMode::TimeAttack(_)) && stats.gems_collected >= TIME_ATTACK_GEMS
+}
+
+fn spawn_time_attack_ui(mut commands: Commands, side_hud: Single<Entity, With<SideHud>>) {
+    commands.entity(*side_hud).with_child((
+        Text::default(),
+        TextFont::default(),
+        HudText(1.0),
+        TextColor(TEXT_COLOR),
+        Visibility::Hidden,
+        TimeAttackUi,
+    ));
+}
+
+fn update_time_attack_ui(
+    mode: Res<GameMode>,
+    stats: Res<Stats>,
+    mut ui: Single<(&mut Text, &mut Visibility), With<TimeAttackUi>>,
+) {
+    let (ref mut text, ref mut visibility) = *ui;
+    if !matches!(*mode, GameMode::TimeAttack(_)) {
+        **visibility = Visibility::Hidden;
+        return;
+    }
+
+    **visibility = Visibility::Inherited;
+    let left = TIME_ATTACK_GEMS.saturating_sub(stats.gems_collected);
+    text.0 = format!("Gems left: {left}  Time: {:.1}s", stats.elapsed);
+}
+
+fn finish_time_attack(stats: Res<Stats>, mut next_state: ResMut<NextState<GameState>>) {
+    if stats.gems_collected >= TIME_ATTACK_GEMS {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+/// Keep the time if it beats the record. Assisted and cheated runs don't
+/// count, like on the leaderboard.
+fn record_time_attack(
+    mode: Res<GameMode>,
+    stats: Res<Stats>,
+    settings: Res<GameSettings>,
+    flags: Res<RunFlags>,
+    mut save: ResMut<SaveData>,
+) {
+    if !finished(&mode, &stats) || settings.assist_mode == AssistMode::On || flags.cheated {
+        return;
+    }
+    if save
+        .best_time_attack
+        .is_some_and(|best| best <= stats.elapsed)
+    {
+        return;
+    }
+
+    save.best_time_attack = Some(stats.elapsed);
+    save.save();
+}
+
+/// Compare the run against the best time on the game over screen
+fn show_time_attack_result(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    stats: Res<Stats>,
+    save: Res<SaveData>,
+) {
+    let result = if finished(&mode, &stats) {
+        format!("{TIME_ATTACK_GEMS} gems in {:.2}s", stats.elapsed)
+    } else {
+        format!(
+            "{} of {TIME_ATTACK_GEMS} gems collected",
+            stats.gems_collected
+        )
+    };
+    let best = match save.best_time_attack {
+        Some(best) => format!(" (best {best:.2}s)"),
+        None => String::new(),
+    };
+
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::new(format!("Time attack: {result}{best}")),
+        TextFont::default(),
+        HudText(1.0),
+        TextColor(TEXT_COLOR),
+        StateScoped(GameState::GameOver),
+    ));
+}
@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+
+use crate::{settings::GameSettings, GameState, BACKGROUND_COLOR};
+
+/// Seconds of play it takes to go from day to night
+const DAY_LENGTH: f32 = 240.0;
+
+/// Background colours at day, dusk and night, spread evenly over the run
+const SKY_COLORS: [Srgba; 3] = [
+    Srgba::rgb(0.9, 0.9, 0.9),
+    Srgba::rgb(0.93, 0.8, 0.7),
+    Srgba::rgb(0.55, 0.58, 0.72),
+];
+/// Tint laid over the level at the same points, kept faint so gems and
+/// obstacles stay easy to read
+const TINT_COLORS: [Srgba; 3] = [
+    Srgba::new(1.0, 0.7, 0.4, 0.0),
+    Srgba::new(1.0, 0.6, 0.3, 0.08),
+    Srgba::new(0.1, 0.1, 0.35, 0.18),
+];
+
+/// Plugin to shift the lighting from day to night as a run goes on
+pub struct TimeOfDayPlugin;
+
+impl Plugin for TimeOfDayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>()
+            .add_systems(Startup, spawn_tint)
+            .add_systems(
+                Update,
+                advance_time_of_day.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(Update, apply_lighting);
+    }
+}
+
+/// How far through the run's day it is, from 0 at midday to 1 at night
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct TimeOfDay(f32);
+
+/// Full screen overlay tinting the level. It sits below the rest of the UI,
+/// so the HUD keeps its colours.
+#[derive(Component)]
+struct LightingTint;
+
+fn spawn_tint(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(Color::NONE),
+        GlobalZIndex(-1),
+        LightingTint,
+    ));
+}
+
+fn advance_time_of_day(mut time_of_day: ResMut<TimeOfDay>, time: Res<Time>) {
+    **time_of_day = (**time_of_day + time.delta_secs() / DAY_LENGTH).min(1.0);
+}
+
+/// Colour `t` of the way through `colors`, which are spaced evenly
+fn sample(colors: &[Srgba; 3], t: f32) -> Color {
+    let scaled = t.clamp(0.0, 1.0) * (colors.len() - 1) as f32;
+    let index = (scaled as usize).min(colors.len() - 2);
+    colors[index]
+        .mix(&colors[index + 1], scaled - index as f32)
+        .into()
+}
+
+fn apply_lighting(
+    time_of_day: Res<TimeOfDay>,
+    settings: Res<GameSettings>,
+    mut clear_color: ResMut<ClearColor>,
+    mut tint: Single<&mut BackgroundColor, With<LightingTint>>,
+) {
+    if !time_of_day.is_changed() && !settings.is_changed() {
+        return;
+    }
+
+    if settings.time_of_day {
+        clear_color.0 = sample(&SKY_COLORS, **time_of_day);
+        tint.0 = sample(&TINT_COLORS, **time_of_day);
+    } else {
+        clear_color.0 = BACKGROUND_COLOR;
+        tint.0 = Color::NONE;
+    }
+}